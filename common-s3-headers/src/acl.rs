@@ -0,0 +1,498 @@
+//! Access control lists: canned ACLs, grant headers and the
+//! `AccessControlPolicy` document.
+//!
+use crate::aws_format::{self, push_element};
+use crate::prelude::*;
+use crate::UnknownValueError;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use core::{fmt, str::FromStr};
+use md5::{Digest, Md5};
+
+/// The group of every user, signed or not.
+pub const ALL_USERS_URI: &str = "http://acs.amazonaws.com/groups/global/AllUsers";
+/// The group of every signed request from any AWS account.
+pub const AUTHENTICATED_USERS_URI: &str = "http://acs.amazonaws.com/groups/global/AuthenticatedUsers";
+/// The group that writes server access logs.
+pub const LOG_DELIVERY_URI: &str = "http://acs.amazonaws.com/groups/s3/LogDelivery";
+
+/// A predefined ACL, sent as `x-amz-acl`. Can't be combined with grants.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::CannedAcl;
+///
+/// let header = CannedAcl::BucketOwnerFullControl.to_header();
+/// assert_eq!(header, ("x-amz-acl", "bucket-owner-full-control".to_owned()));
+///
+/// let acl: CannedAcl = "public-read".parse().unwrap();
+/// assert_eq!(acl, CannedAcl::PublicRead);
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/userguide/acl-overview.html#canned-acl
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CannedAcl {
+  #[default]
+  Private,
+  PublicRead,
+  PublicReadWrite,
+  AuthenticatedRead,
+  AwsExecRead,
+  BucketOwnerRead,
+  BucketOwnerFullControl,
+  LogDeliveryWrite,
+}
+
+impl CannedAcl {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CannedAcl::Private => "private",
+      CannedAcl::PublicRead => "public-read",
+      CannedAcl::PublicReadWrite => "public-read-write",
+      CannedAcl::AuthenticatedRead => "authenticated-read",
+      CannedAcl::AwsExecRead => "aws-exec-read",
+      CannedAcl::BucketOwnerRead => "bucket-owner-read",
+      CannedAcl::BucketOwnerFullControl => "bucket-owner-full-control",
+      CannedAcl::LogDeliveryWrite => "log-delivery-write",
+    }
+  }
+
+  pub fn to_header(&self) -> (&'static str, String) {
+    ("x-amz-acl", self.as_str().to_owned())
+  }
+}
+
+impl fmt::Display for CannedAcl {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for CannedAcl {
+  type Err = UnknownValueError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "private" => Ok(CannedAcl::Private),
+      "public-read" => Ok(CannedAcl::PublicRead),
+      "public-read-write" => Ok(CannedAcl::PublicReadWrite),
+      "authenticated-read" => Ok(CannedAcl::AuthenticatedRead),
+      "aws-exec-read" => Ok(CannedAcl::AwsExecRead),
+      "bucket-owner-read" => Ok(CannedAcl::BucketOwnerRead),
+      "bucket-owner-full-control" => Ok(CannedAcl::BucketOwnerFullControl),
+      "log-delivery-write" => Ok(CannedAcl::LogDeliveryWrite),
+      _ => Err(UnknownValueError(s.to_owned())),
+    }
+  }
+}
+
+/// What a grant allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+  Read,
+  Write,
+  ReadAcp,
+  WriteAcp,
+  FullControl,
+}
+
+impl Permission {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Permission::Read => "READ",
+      Permission::Write => "WRITE",
+      Permission::ReadAcp => "READ_ACP",
+      Permission::WriteAcp => "WRITE_ACP",
+      Permission::FullControl => "FULL_CONTROL",
+    }
+  }
+
+  /// The `x-amz-grant-*` header for this permission.
+  pub fn header_name(&self) -> &'static str {
+    match self {
+      Permission::Read => "x-amz-grant-read",
+      Permission::Write => "x-amz-grant-write",
+      Permission::ReadAcp => "x-amz-grant-read-acp",
+      Permission::WriteAcp => "x-amz-grant-write-acp",
+      Permission::FullControl => "x-amz-grant-full-control",
+    }
+  }
+}
+
+impl fmt::Display for Permission {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for Permission {
+  type Err = UnknownValueError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "READ" => Ok(Permission::Read),
+      "WRITE" => Ok(Permission::Write),
+      "READ_ACP" => Ok(Permission::ReadAcp),
+      "WRITE_ACP" => Ok(Permission::WriteAcp),
+      "FULL_CONTROL" => Ok(Permission::FullControl),
+      _ => Err(UnknownValueError(s.to_owned())),
+    }
+  }
+}
+
+/// Who a grant is for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Grantee {
+  /// An AWS account, by its canonical user ID.
+  CanonicalUser(String),
+  /// A predefined group, such as `ALL_USERS_URI`.
+  Group(String),
+  /// An AWS account, by its email address. Only in some regions.
+  Email(String),
+}
+
+impl Grantee {
+  pub fn id<S: Into<String>>(value: S) -> Self {
+    Grantee::CanonicalUser(value.into())
+  }
+
+  pub fn uri<S: Into<String>>(value: S) -> Self {
+    Grantee::Group(value.into())
+  }
+
+  pub fn email_address<S: Into<String>>(value: S) -> Self {
+    Grantee::Email(value.into())
+  }
+
+  pub fn all_users() -> Self {
+    Self::uri(ALL_USERS_URI)
+  }
+
+  pub fn authenticated_users() -> Self {
+    Self::uri(AUTHENTICATED_USERS_URI)
+  }
+
+  pub fn log_delivery() -> Self {
+    Self::uri(LOG_DELIVERY_URI)
+  }
+
+  /// The `xsi:type` and element name in an `AccessControlPolicy`.
+  fn xml_type(&self) -> (&'static str, &'static str, &str) {
+    match self {
+      Grantee::CanonicalUser(id) => ("CanonicalUser", "ID", id),
+      Grantee::Group(uri) => ("Group", "URI", uri),
+      Grantee::Email(email) => ("AmazonCustomerByEmail", "EmailAddress", email),
+    }
+  }
+}
+
+/// As in a grant header, such as `id="79a59df9..."`.
+impl fmt::Display for Grantee {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Grantee::CanonicalUser(id) => write!(f, "id=\"{}\"", id),
+      Grantee::Group(uri) => write!(f, "uri=\"{}\"", uri),
+      Grantee::Email(email) => write!(f, "emailAddress=\"{}\"", email),
+    }
+  }
+}
+
+/// Explicit grants, sent as `x-amz-grant-*` headers. Can't be combined with a
+/// canned ACL.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{Grantee, Grants};
+///
+/// let headers = Grants::new()
+///   .set_read(Grantee::all_users())
+///   .set_full_control(Grantee::id("79a59df900b949e55d96a1e698fbacedfd6e09d98eacf8f8d5218e7cd47ef2be"))
+///   .set_full_control(Grantee::email_address("partner@example.com"))
+///   .to_headers();
+///
+/// assert_eq!(headers, vec![
+///   ("x-amz-grant-read", "uri=\"http://acs.amazonaws.com/groups/global/AllUsers\"".to_owned()),
+///   (
+///     "x-amz-grant-full-control",
+///     "id=\"79a59df900b949e55d96a1e698fbacedfd6e09d98eacf8f8d5218e7cd47ef2be\", \
+///      emailAddress=\"partner@example.com\"".to_owned(),
+///   ),
+/// ]);
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectAcl.html#API_PutObjectAcl_RequestSyntax
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Grants {
+  /// In the order they were set.
+  pub grants: Vec<(Permission, Grantee)>,
+}
+
+impl Grants {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a grant, unless it was already added.
+  pub fn set_grant(mut self, permission: Permission, grantee: Grantee) -> Self {
+    let grant = (permission, grantee);
+    if !self.grants.contains(&grant) {
+      self.grants.push(grant);
+    }
+    self
+  }
+
+  pub fn set_read(self, grantee: Grantee) -> Self {
+    self.set_grant(Permission::Read, grantee)
+  }
+
+  pub fn set_write(self, grantee: Grantee) -> Self {
+    self.set_grant(Permission::Write, grantee)
+  }
+
+  pub fn set_read_acp(self, grantee: Grantee) -> Self {
+    self.set_grant(Permission::ReadAcp, grantee)
+  }
+
+  pub fn set_write_acp(self, grantee: Grantee) -> Self {
+    self.set_grant(Permission::WriteAcp, grantee)
+  }
+
+  pub fn set_full_control(self, grantee: Grantee) -> Self {
+    self.set_grant(Permission::FullControl, grantee)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.grants.is_empty()
+  }
+
+  /// Gets one header per permission, with its grantees separated by commas.
+  pub fn to_headers(&self) -> Vec<(&'static str, String)> {
+    let mut permissions: Vec<Permission> = self.grants.iter().map(|(permission, _)| *permission).collect();
+    permissions.sort();
+    permissions.dedup();
+    permissions
+      .into_iter()
+      .map(|permission| {
+        let grantees: Vec<String> = self
+          .grants
+          .iter()
+          .filter(|(p, _)| *p == permission)
+          .map(|(_, grantee)| grantee.to_string())
+          .collect();
+        (permission.header_name(), grantees.join(", "))
+      })
+      .collect()
+  }
+}
+
+/// The body of a PutObjectAcl or PutBucketAcl request, replacing the whole
+/// ACL. Send it with `Content-Type: application/xml` and its `Content-MD5`.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{AccessControlPolicy, Grantee, Permission};
+///
+/// let policy = AccessControlPolicy::new("79a59df900b949e55d96a1e698fbacedfd6e09d98eacf8f8d5218e7cd47ef2be")
+///   .set_grant(Permission::FullControl, Grantee::id("79a59df900b949e55d96a1e698fbacedfd6e09d98eacf8f8d5218e7cd47ef2be"))
+///   .set_grant(Permission::Read, Grantee::all_users());
+/// let xml = policy.to_xml();
+///
+/// assert!(xml.starts_with("<AccessControlPolicy xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Owner>"));
+/// assert!(xml.contains(
+///   "<Grant><Grantee xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:type=\"Group\">\
+///    <URI>http://acs.amazonaws.com/groups/global/AllUsers</URI></Grantee>\
+///    <Permission>READ</Permission></Grant>"
+/// ));
+/// assert_eq!(policy.to_headers()[0], ("Content-Type", "application/xml".to_owned()));
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObjectAcl.html
+/// * https://docs.aws.amazon.com/AmazonS3/latest/API/API_AccessControlPolicy.html
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessControlPolicy {
+  /// The canonical user ID of the owner.
+  pub owner_id: String,
+  pub owner_display_name: Option<String>,
+  pub grants: Grants,
+}
+
+impl AccessControlPolicy {
+  pub fn new<S: Into<String>>(owner_id: S) -> Self {
+    Self {
+      owner_id: owner_id.into(),
+      owner_display_name: None,
+      grants: Grants::new(),
+    }
+  }
+
+  pub fn set_owner_display_name<S: Into<String>>(mut self, value: S) -> Self {
+    self.owner_display_name = Some(value.into());
+    self
+  }
+
+  pub fn set_grant(mut self, permission: Permission, grantee: Grantee) -> Self {
+    self.grants = self.grants.set_grant(permission, grantee);
+    self
+  }
+
+  pub fn set_grants(mut self, grants: Grants) -> Self {
+    self.grants = grants;
+    self
+  }
+
+  pub fn to_xml(&self) -> String {
+    let mut xml = String::from("<AccessControlPolicy xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Owner>");
+    push_element(&mut xml, "ID", &self.owner_id);
+    if let Some(display_name) = &self.owner_display_name {
+      push_element(&mut xml, "DisplayName", display_name);
+    }
+    xml.push_str("</Owner><AccessControlList>");
+    for (permission, grantee) in &self.grants.grants {
+      let (xsi_type, element, value) = grantee.xml_type();
+      xml.push_str("<Grant><Grantee xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:type=\"");
+      xml.push_str(xsi_type);
+      xml.push_str("\">");
+      push_element(&mut xml, element, value);
+      xml.push_str("</Grantee>");
+      push_element(&mut xml, "Permission", permission.as_str());
+      xml.push_str("</Grant>");
+    }
+    xml.push_str("</AccessControlList></AccessControlPolicy>");
+    xml
+  }
+
+  /// Gets the `Content-Type` and `Content-MD5` headers for the body from
+  /// `to_xml`. S3 requires `Content-MD5` on PutBucketAcl.
+  pub fn to_headers(&self) -> Vec<(&'static str, String)> {
+    vec![
+      ("Content-Type", "application/xml".to_owned()),
+      ("Content-MD5", BASE64.encode(Md5::digest(self.to_xml().as_bytes()))),
+    ]
+  }
+}
+
+/// Reads the grants of a GetObjectAcl or GetBucketAcl response. Grants with an
+/// unknown grantee type or permission are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{parse_access_control_policy, Grantee, Permission};
+///
+/// let body = r#"<AccessControlPolicy><Owner><ID>abc</ID></Owner><AccessControlList>
+///   <Grant>
+///     <Grantee xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:type="CanonicalUser">
+///       <ID>abc</ID><DisplayName>owner</DisplayName>
+///     </Grantee>
+///     <Permission>FULL_CONTROL</Permission>
+///   </Grant>
+/// </AccessControlList></AccessControlPolicy>"#;
+///
+/// let policy = parse_access_control_policy(body).unwrap();
+/// assert_eq!(policy.owner_id, "abc");
+/// assert_eq!(policy.grants.grants, vec![(Permission::FullControl, Grantee::id("abc"))]);
+/// ```
+///
+pub fn parse_access_control_policy(body: &str) -> Option<AccessControlPolicy> {
+  let owner = aws_format::get_element_text(body, "Owner")?;
+  let mut policy = AccessControlPolicy::new(aws_format::xml_unescape(aws_format::get_element_text(owner, "ID")?));
+  if let Some(display_name) = aws_format::get_element_text(owner, "DisplayName") {
+    policy = policy.set_owner_display_name(aws_format::xml_unescape(display_name));
+  }
+
+  for grant in aws_format::get_elements(body, "Grant") {
+    let Some(permission) = aws_format::get_element_text(grant, "Permission").and_then(|p| p.parse().ok()) else {
+      continue;
+    };
+    let text = |element: &str| aws_format::get_element_text(grant, element).map(aws_format::xml_unescape);
+    let grantee = if grant.contains("\"CanonicalUser\"") {
+      text("ID").map(Grantee::CanonicalUser)
+    } else if grant.contains("\"Group\"") {
+      text("URI").map(Grantee::Group)
+    } else if grant.contains("\"AmazonCustomerByEmail\"") {
+      text("EmailAddress").map(Grantee::Email)
+    } else {
+      None
+    };
+    if let Some(grantee) = grantee {
+      policy = policy.set_grant(permission, grantee);
+    }
+  }
+  Some(policy)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common_testing::assert;
+
+  #[test]
+  fn canned_acl_round_trips() {
+    for acl in [
+      CannedAcl::Private,
+      CannedAcl::PublicRead,
+      CannedAcl::PublicReadWrite,
+      CannedAcl::AuthenticatedRead,
+      CannedAcl::AwsExecRead,
+      CannedAcl::BucketOwnerRead,
+      CannedAcl::BucketOwnerFullControl,
+      CannedAcl::LogDeliveryWrite,
+    ] {
+      assert::equal(acl.as_str().parse::<CannedAcl>(), Ok(acl));
+    }
+    assert::err(&"public".parse::<CannedAcl>());
+  }
+
+  #[test]
+  fn to_headers_orders_by_permission_and_skips_duplicates() {
+    let headers = Grants::new()
+      .set_write_acp(Grantee::id("a"))
+      .set_read(Grantee::log_delivery())
+      .set_read(Grantee::log_delivery())
+      .set_write(Grantee::log_delivery())
+      .set_read_acp(Grantee::authenticated_users())
+      .to_headers();
+
+    assert::equal(
+      headers,
+      vec![
+        ("x-amz-grant-read", format!("uri=\"{}\"", LOG_DELIVERY_URI)),
+        ("x-amz-grant-write", format!("uri=\"{}\"", LOG_DELIVERY_URI)),
+        ("x-amz-grant-read-acp", format!("uri=\"{}\"", AUTHENTICATED_USERS_URI)),
+        ("x-amz-grant-write-acp", "id=\"a\"".to_owned()),
+      ],
+    );
+  }
+
+  #[test]
+  fn to_xml_escapes_and_round_trips() {
+    let policy = AccessControlPolicy::new("abc")
+      .set_owner_display_name("Tom & Jerry")
+      .set_grant(Permission::FullControl, Grantee::id("abc"))
+      .set_grant(Permission::Read, Grantee::email_address("partner@example.com"))
+      .set_grant(Permission::Write, Grantee::log_delivery());
+    let xml = policy.to_xml();
+
+    assert!(xml.contains("<DisplayName>Tom &amp; Jerry</DisplayName>"));
+    assert!(xml.contains("xsi:type=\"AmazonCustomerByEmail\"><EmailAddress>partner@example.com</EmailAddress>"));
+    assert::equal(parse_access_control_policy(&xml), Some(policy));
+  }
+
+  #[test]
+  fn to_headers_has_content_md5_of_xml() {
+    let policy = AccessControlPolicy::new("abc");
+    let md5 = BASE64.encode(Md5::digest(policy.to_xml().as_bytes()));
+    assert::equal(policy.to_headers()[1].clone(), ("Content-MD5", md5));
+  }
+}
@@ -1,8 +1,12 @@
+use crate::prelude::*;
+use alloc::borrow::Cow;
+
 /// Given a list of headers, returns headers that match the AWS spec. This includes:
 /// - Lowercasing all keys.
 /// - Removing all headers that don't start with "x-amz-" or are "host", "content-type", or "range".
 /// - Sorting the headers by key.
 /// - Trimming whitespace from values.
+/// - Joining repeated headers into one comma-separated value, in order.
 ///
 /// # Examples
 ///
@@ -19,30 +23,134 @@
 /// assert_eq!(
 ///  canonical_headers,
 ///  vec![
-///   ("host".to_owned(), "examplebucket.s3.amazonaws.com"),
-///   ("range".to_owned(), "bytes=0-9"),
-///   ("x-amz-date".to_owned(), "20130524T000000Z"),
-///   ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY"),
+///   ("host".to_owned(), "examplebucket.s3.amazonaws.com".into()),
+///   ("range".to_owned(), "bytes=0-9".into()),
+///   ("x-amz-date".to_owned(), "20130524T000000Z".into()),
+///   ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY".into()),
 ///  ]
 /// );
 /// ```
 ///
-pub fn to_canonical_headers<K: AsRef<str>, V: AsRef<str>>(headers: &[(K, V)]) -> Vec<(std::string::String, &str)> {
-  let mut canonical_headers = headers
+pub fn to_canonical_headers<K: AsRef<str>, V: AsRef<str>>(headers: &[(K, V)]) -> Vec<(String, Cow<'_, str>)> {
+  to_canonical_headers_with_policy(headers, HeaderPolicy::Minimal)
+}
+
+/// Headers that are never signed with `HeaderPolicy::All`, because proxies and
+/// clients can change them after signing.
+pub const UNSIGNABLE_HEADERS: &[&str] = &[
+  "authorization",
+  "connection",
+  "expect",
+  "transfer-encoding",
+  "user-agent",
+  "x-amzn-trace-id",
+];
+
+/// Which headers are signed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderPolicy {
+  /// Only "host", "content-type", "range" and "x-amz-*" headers. Others, such
+  /// as `If-Match`, are sent but not signed.
+  #[default]
+  Minimal,
+  /// Every header except `UNSIGNABLE_HEADERS`. Use this when headers such as
+  /// `If-None-Match` must not be changed in transit.
+  All,
+}
+
+impl HeaderPolicy {
+  /// Whether a lowercase header name is signed.
+  pub fn is_signed(&self, key: &str) -> bool {
+    match self {
+      HeaderPolicy::Minimal => key.starts_with("x-amz-") || key == "host" || key == "content-type" || key == "range",
+      HeaderPolicy::All => !UNSIGNABLE_HEADERS.contains(&key),
+    }
+  }
+}
+
+/// Like `to_canonical_headers`, but keeps the headers the policy signs.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::aws_canonical::{to_canonical_headers_with_policy, HeaderPolicy};
+///
+/// let headers = vec![
+///  ("Host", "examplebucket.s3.amazonaws.com"),
+///  ("If-None-Match", "*"),
+///  ("User-Agent", "test"),
+/// ];
+/// assert_eq!(
+///  to_canonical_headers_with_policy(&headers, HeaderPolicy::All),
+///  vec![
+///   ("host".to_owned(), "examplebucket.s3.amazonaws.com".into()),
+///   ("if-none-match".to_owned(), "*".into()),
+///  ]
+/// );
+/// ```
+///
+pub fn to_canonical_headers_with_policy<K: AsRef<str>, V: AsRef<str>>(
+  headers: &[(K, V)],
+  policy: HeaderPolicy,
+) -> Vec<(String, Cow<'_, str>)> {
+  let mut signed = headers
     .iter()
     .filter_map(|(k, v)| {
       let key = k.as_ref().to_lowercase();
-      if key.starts_with("x-amz-") || key == "host" || key == "content-type" || key == "range" {
+      if policy.is_signed(&key) {
         Some((key, v.as_ref().trim()))
       } else {
         None
       }
     })
     .collect::<Vec<(String, &str)>>();
-  canonical_headers.sort();
+  // Stable, so repeated headers keep their order.
+  signed.sort_by(|a, b| a.0.cmp(&b.0));
+
+  let mut canonical_headers: Vec<(String, Cow<str>)> = Vec::with_capacity(signed.len());
+  for (key, value) in signed {
+    match canonical_headers.last_mut() {
+      Some((last_key, last_value)) if *last_key == key => {
+        let last_value = last_value.to_mut();
+        last_value.push(',');
+        last_value.push_str(value);
+      }
+      _ => canonical_headers.push((key, Cow::Borrowed(value))),
+    }
+  }
   canonical_headers
 }
 
+/// Reads the headers of an HTTP client's header map for signing, one entry
+/// per value, trimmed. Repeated headers are joined when they are signed. Fails
+/// with the name of the first header whose value isn't UTF-8, since it can't
+/// be signed as sent.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::aws_canonical::to_signable_headers;
+///
+/// let headers = vec![("x-amz-meta-a", &b" 1 "[..]), ("x-amz-meta-a", &b"2"[..])];
+/// assert_eq!(to_signable_headers(headers), Ok(vec![("x-amz-meta-a", "1"), ("x-amz-meta-a", "2")]));
+///
+/// let headers = vec![("x-amz-meta-a", &b"\xff"[..])];
+/// assert_eq!(to_signable_headers(headers), Err("x-amz-meta-a"));
+/// ```
+///
+pub fn to_signable_headers<'h, N, I>(headers: I) -> Result<Vec<(N, &'h str)>, N>
+where
+  I: IntoIterator<Item = (N, &'h [u8])>,
+{
+  headers
+    .into_iter()
+    .map(|(name, value)| match core::str::from_utf8(value) {
+      Ok(value) => Ok((name, value.trim())),
+      Err(_) => Err(name),
+    })
+    .collect()
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -60,10 +168,10 @@ mod tests {
     assert::equal(
       canonical_headers,
       vec![
-        ("host".to_owned(), "examplebucket.s3.amazonaws.com"),
-        ("range".to_owned(), "bytes=0-9"),
-        ("x-amz-date".to_owned(), "20130524T000000Z"),
-        ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY"),
+        ("host".to_owned(), "examplebucket.s3.amazonaws.com".into()),
+        ("range".to_owned(), "bytes=0-9".into()),
+        ("x-amz-date".to_owned(), "20130524T000000Z".into()),
+        ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY".into()),
       ],
     );
   }
@@ -87,11 +195,11 @@ mod tests {
     assert::equal(
       result,
       vec![
-        ("content-type".to_owned(), "application/json"),
-        ("host".to_owned(), "s3.etc"),
-        ("x-amz-date".to_owned(), "19700101T000000Z"),
-        ("x-amz-special".to_owned(), "a"),
-        ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY"),
+        ("content-type".to_owned(), "application/json".into()),
+        ("host".to_owned(), "s3.etc".into()),
+        ("x-amz-date".to_owned(), "19700101T000000Z".into()),
+        ("x-amz-special".to_owned(), "a".into()),
+        ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY".into()),
       ],
     )
   }
@@ -109,9 +217,9 @@ mod tests {
     assert::equal(
       result,
       vec![
-        ("content-type".to_owned(), "application/json"),
-        ("host".to_owned(), "s3.etc"),
-        ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY"),
+        ("content-type".to_owned(), "application/json".into()),
+        ("host".to_owned(), "s3.etc".into()),
+        ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY".into()),
       ],
     )
   }
@@ -129,9 +237,9 @@ mod tests {
     assert::equal(
       result,
       vec![
-        ("content-type".to_owned(), "application/json"),
-        ("host".to_owned(), "s3.etc"),
-        ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY"),
+        ("content-type".to_owned(), "application/json".into()),
+        ("host".to_owned(), "s3.etc".into()),
+        ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY".into()),
       ],
     )
   }
@@ -151,9 +259,29 @@ mod tests {
     assert::equal(
       result,
       vec![
-        ("content-type".to_owned(), "application/json"),
-        ("host".to_owned(), borrow1),
-        ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY"),
+        ("content-type".to_owned(), "application/json".into()),
+        ("host".to_owned(), borrow1.into()),
+        ("x-amz-storage-class".to_owned(), "REDUCED_REDUNDANCY".into()),
+      ],
+    )
+  }
+
+  #[test]
+  fn repeated_headers_are_joined_in_order() {
+    let headers = &[
+      ("My-Header1", "value2"),
+      ("Host", "example.amazonaws.com"),
+      ("my-header1", " value2 "),
+      ("My-Header1", "value1"),
+    ];
+
+    let result = to_canonical_headers_with_policy(headers, HeaderPolicy::All);
+
+    assert::equal(
+      result,
+      vec![
+        ("host".to_owned(), "example.amazonaws.com".into()),
+        ("my-header1".to_owned(), "value2,value2,value1".into()),
       ],
     )
   }
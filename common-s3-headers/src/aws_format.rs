@@ -2,14 +2,22 @@
 //!
 //! Any creation of strings goes here.
 //!
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use core::fmt::Write;
 use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
-use std::ops::Add;
-use time::{macros::format_description, OffsetDateTime};
+use time::OffsetDateTime;
 use url::Url;
 
-use crate::aws_math::get_sha256;
+use crate::{
+  aws_math::get_sha256,
+  aws_stream::{write_long_datetime, write_short_datetime},
+};
 
-const SHORT_DATE: &[time::format_description::FormatItem<'static>] = format_description!("[year][month][day]");
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const MONTHS: [&str; 12] = [
+  "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
 
 /// Convert a `time::OffsetDateTime` to a short date string. This is used in
 /// the AWS credential scope. It is always UTC, YYYYMMDD, sortable and
@@ -31,14 +39,11 @@ const SHORT_DATE: &[time::format_description::FormatItem<'static>] = format_desc
 /// ```
 ///
 pub fn to_short_datetime(datetime: &OffsetDateTime) -> String {
-  datetime
-    .format(SHORT_DATE)
-    .expect("All dates can be represented as short.")
+  let mut result = String::with_capacity(8);
+  write_short_datetime(&mut result, datetime).expect("Writing to a String never fails");
+  result
 }
 
-const LONG_DATETIME: &[time::format_description::FormatItem<'static>] =
-  time::macros::format_description!("[year][month][day]T[hour][minute][second]Z");
-
 /// Convert a `time::OffsetDateTime` to a long date string. This is used in
 /// the AWS credential scope. It is always UTC, YYYYMMDD'T'HHMMSS'Z', sortable
 /// and lexicographically comparable.
@@ -59,16 +64,232 @@ const LONG_DATETIME: &[time::format_description::FormatItem<'static>] =
 /// ```
 ///
 pub fn to_long_datetime(datetime: &OffsetDateTime) -> String {
-  datetime
-    .format(LONG_DATETIME)
-    .expect("All dates can be represented as long.")
+  let mut result = String::with_capacity(16);
+  write_long_datetime(&mut result, datetime).expect("Writing to a String never fails");
+  result
+}
+
+/// Convert a `time::OffsetDateTime` to an ISO 8601 date string. This is used
+/// in header values and documents, such as the object lock retain-until date.
+/// It is always UTC, YYYY-MM-DD'T'HH:MM:SS'Z'.
+///
+/// # Examples
+///
+/// ```
+/// use time::OffsetDateTime;
+/// use common_s3_headers::aws_format::to_iso8601_datetime;
+///
+/// let datetime = OffsetDateTime::from_unix_timestamp(0).unwrap();
+/// let result = to_iso8601_datetime(&datetime);
+/// assert_eq!(result, "1970-01-01T00:00:00Z");
+///
+/// let datetime = OffsetDateTime::from_unix_timestamp(1_000_000_000).unwrap();
+/// let result = to_iso8601_datetime(&datetime);
+/// assert_eq!(result, "2001-09-09T01:46:40Z");
+/// ```
+///
+pub fn to_iso8601_datetime(datetime: &OffsetDateTime) -> String {
+  let datetime = datetime.to_offset(time::UtcOffset::UTC);
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+    datetime.year(),
+    u8::from(datetime.month()),
+    datetime.day(),
+    datetime.hour(),
+    datetime.minute(),
+    datetime.second()
+  )
+}
+
+const HTTP_DATE: &[time::format_description::FormatItem<'static>] = time::macros::format_description!(
+  "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+);
+
+/// Convert a `time::OffsetDateTime` to an HTTP date string (RFC 7231
+/// IMF-fixdate). This is used in the `Date` header and conditional request
+/// headers. It is always UTC.
+///
+/// # Examples
+///
+/// ```
+/// use time::OffsetDateTime;
+/// use common_s3_headers::aws_format::to_http_date;
+///
+/// let datetime = OffsetDateTime::from_unix_timestamp(0).unwrap();
+/// let result = to_http_date(&datetime);
+/// assert_eq!(result, "Thu, 01 Jan 1970 00:00:00 GMT");
+///
+/// let datetime = OffsetDateTime::from_unix_timestamp(1_000_000_000).unwrap();
+/// let result = to_http_date(&datetime);
+/// assert_eq!(result, "Sun, 09 Sep 2001 01:46:40 GMT");
+/// ```
+///
+pub fn to_http_date(datetime: &OffsetDateTime) -> String {
+  let datetime = datetime.to_offset(time::UtcOffset::UTC);
+  let mut result = String::with_capacity(29);
+  write!(
+    result,
+    "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+    WEEKDAYS[datetime.weekday().number_days_from_monday() as usize],
+    datetime.day(),
+    MONTHS[u8::from(datetime.month()) as usize - 1],
+    datetime.year(),
+    datetime.hour(),
+    datetime.minute(),
+    datetime.second()
+  )
+  .expect("Writing to a String never fails");
+  result
+}
+
+/// Parse an HTTP date string (RFC 7231 IMF-fixdate), such as a `Date`
+/// response header. Returns `None` for any other format.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::aws_format::parse_http_date;
+///
+/// let result = parse_http_date("Sun, 09 Sep 2001 01:46:40 GMT").unwrap();
+/// assert_eq!(result.unix_timestamp(), 1_000_000_000);
+///
+/// assert!(parse_http_date("2001-09-09T01:46:40Z").is_none());
+/// ```
+///
+pub fn parse_http_date(value: &str) -> Option<OffsetDateTime> {
+  time::PrimitiveDateTime::parse(value.trim(), HTTP_DATE)
+    .ok()
+    .map(|datetime| datetime.assume_utc())
+}
+
+/// Parse an ISO 8601 date string, such as the `ServerTime` of an error
+/// document. Fractional seconds are allowed.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::aws_format::parse_iso8601_datetime;
+///
+/// let result = parse_iso8601_datetime("2001-09-09T01:46:40Z").unwrap();
+/// assert_eq!(result.unix_timestamp(), 1_000_000_000);
+///
+/// let result = parse_iso8601_datetime("2001-09-09T01:46:40.500Z").unwrap();
+/// assert_eq!(result.unix_timestamp(), 1_000_000_000);
+/// ```
+///
+pub fn parse_iso8601_datetime(value: &str) -> Option<OffsetDateTime> {
+  OffsetDateTime::parse(value.trim(), &time::format_description::well_known::Rfc3339).ok()
+}
+
+/// Gets the text of the first element with this name. Error documents are
+/// flat, so this is enough without a full XML parser.
+pub(crate) fn get_element_text<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+  let open = format!("<{}>", name);
+  let close = format!("</{}>", name);
+  let start = body.find(&open)? + open.len();
+  let end = start + body[start..].find(&close)?;
+  Some(body[start..end].trim())
+}
+
+/// Gets the contents of every element with this name. S3 documents are
+/// simple, so this is enough without a full XML parser.
+pub(crate) fn get_elements<'a>(body: &'a str, name: &str) -> Vec<&'a str> {
+  let open = format!("<{}>", name);
+  let close = format!("</{}>", name);
+  let mut result = Vec::new();
+  let mut rest = body;
+  while let Some(start) = rest.find(&open) {
+    rest = &rest[start + open.len()..];
+    let Some(end) = rest.find(&close) else {
+      break;
+    };
+    result.push(&rest[..end]);
+    rest = &rest[end + close.len()..];
+  }
+  result
+}
+
+/// Appends `<name>value</name>`, escaping the value.
+pub(crate) fn push_element(xml: &mut String, name: &str, value: &str) {
+  xml.push('<');
+  xml.push_str(name);
+  xml.push('>');
+  for c in value.chars() {
+    match c {
+      '&' => xml.push_str("&amp;"),
+      '<' => xml.push_str("&lt;"),
+      '>' => xml.push_str("&gt;"),
+      '"' => xml.push_str("&quot;"),
+      '\'' => xml.push_str("&apos;"),
+      c => xml.push(c),
+    }
+  }
+  xml.push_str("</");
+  xml.push_str(name);
+  xml.push('>');
+}
+
+/// Replaces the five predefined XML entities.
+pub(crate) fn xml_unescape(value: &str) -> String {
+  value
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&amp;", "&")
+}
+
+const LONG_DATETIME: &[time::format_description::FormatItem<'static>] =
+  time::macros::format_description!("[year][month][day]T[hour][minute][second]Z");
+
+/// Parse a long datetime string, such as the `x-amz-date` header.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::aws_format::parse_long_datetime;
+///
+/// let result = parse_long_datetime("20010909T014640Z").unwrap();
+/// assert_eq!(result.unix_timestamp(), 1_000_000_000);
+///
+/// assert!(parse_long_datetime("2001-09-09T01:46:40Z").is_none());
+/// ```
+///
+pub fn parse_long_datetime(value: &str) -> Option<OffsetDateTime> {
+  time::PrimitiveDateTime::parse(value.trim(), LONG_DATETIME)
+    .ok()
+    .map(|datetime| datetime.assume_utc())
+}
+
+/// Gets the value of the `Host` header for the URL. The port is included
+/// unless it is the default for the scheme, as HTTP clients send it.
+///
+/// # Examples
+///
+/// ```
+/// use url::Url;
+/// use common_s3_headers::aws_format::host_header_string;
+///
+/// let url = Url::parse("https://jsonlog.s3.amazonaws.com/test.json").unwrap();
+/// assert_eq!(host_header_string(&url), "jsonlog.s3.amazonaws.com");
+///
+/// let url = Url::parse("http://localhost:9000/bucket/key").unwrap();
+/// assert_eq!(host_header_string(&url), "localhost:9000");
+/// ```
+///
+pub fn host_header_string(url: &Url) -> String {
+  let host = url.host_str().unwrap_or_default();
+  match url.port() {
+    Some(port) => format!("{}:{}", host, port),
+    None => host.to_owned(),
+  }
 }
 
 ///
 ///
 /// See https://docs.aws.amazon.com/AmazonS3/latest/userguide/object-keys.html
 /// See https://perishablepress.com/stop-using-unsafe-characters-in-urls/
-const FRAGMENT: &AsciiSet = &CONTROLS
+pub(crate) const FRAGMENT: &AsciiSet = &CONTROLS
   // URL_RESERVED
   .add(b':')
   .add(b'?')
@@ -100,7 +321,7 @@ const FRAGMENT: &AsciiSet = &CONTROLS
   .add(b'^')
   .add(b'`');
 
-const FRAGMENT_SLASH: &AsciiSet = &FRAGMENT.add(b'/');
+pub(crate) const FRAGMENT_SLASH: &AsciiSet = &FRAGMENT.add(b'/');
 
 /// Encode a URI following the specific requirements of the AWS service.
 pub fn uri_encode(string: &str, encode_slash: bool) -> String {
@@ -287,10 +508,10 @@ pub fn get_keys<S: AsRef<str>, T>(headers: &[(S, T)]) -> Vec<&str> {
 /// * https://docs.aws.amazon.com/general/latest/gr/sigv4-create-string-to-sign.html
 /// * https://docs.aws.amazon.com/general/latest/gr/sigv4-add-signature-to-request.html
 ///
-pub fn canonical_request_string<S: AsRef<str>>(
+pub fn canonical_request_string<S: AsRef<str>, T: AsRef<str>>(
   method: &str,
   url: &Url,
-  canonical_headers: &[(S, &str)],
+  canonical_headers: &[(S, T)],
   payload_hash: &str,
 ) -> String {
   format!(
@@ -298,12 +519,55 @@ pub fn canonical_request_string<S: AsRef<str>>(
     method,
     canonical_uri_string(url),
     canonical_query_string(url),
-    to_key_value_strings(canonical_headers, ":").join("\n").add("\n"),
+    to_key_value_strings(canonical_headers, ":").join("\n") + "\n",
     get_keys(canonical_headers).join(";"),
     payload_hash
   )
 }
 
+/// Generate a JSON object string from key-value pairs, escaping as needed. Used
+/// for values S3 expects as JSON, such as the KMS encryption context.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use common_s3_headers::aws_format::json_object_string;
+///
+/// let mut context = BTreeMap::new();
+/// context.insert("a", "b");
+/// context.insert("quote", "\"");
+/// let result = json_object_string(&context);
+/// assert_eq!(result, r#"{"a":"b","quote":"\""}"#);
+/// ```
+///
+pub fn json_object_string<K: AsRef<str>, V: AsRef<str>>(values: &BTreeMap<K, V>) -> String {
+  let pairs: Vec<String> = values
+    .iter()
+    .map(|(k, v)| format!("{}:{}", json_string(k.as_ref()), json_string(v.as_ref())))
+    .collect();
+  format!("{{{}}}", pairs.join(","))
+}
+
+/// Generate a quoted JSON string, escaping as needed.
+pub fn json_string(value: &str) -> String {
+  let mut result = String::with_capacity(value.len() + 2);
+  result.push('"');
+  for c in value.chars() {
+    match c {
+      '"' => result.push_str("\\\""),
+      '\\' => result.push_str("\\\\"),
+      '\n' => result.push_str("\\n"),
+      '\r' => result.push_str("\\r"),
+      '\t' => result.push_str("\\t"),
+      c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+      c => result.push(c),
+    }
+  }
+  result.push('"');
+  result
+}
+
 /// Get the security token string. It is always &X-Amz-Security-Token=token with percent encoding.
 ///
 /// # Examples
@@ -377,7 +641,7 @@ mod tests {
   use super::*;
   use crate::aws_canonical::to_canonical_headers;
   use common_testing::assert;
-  use std::str::FromStr;
+  use core::str::FromStr;
 
   #[test]
   fn to_short_datetime_works() {
@@ -401,6 +665,36 @@ mod tests {
     assert_eq!(result, "20010909T014640Z");
   }
 
+  #[test]
+  fn to_iso8601_datetime_works() {
+    let datetime = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let result = to_iso8601_datetime(&datetime);
+    assert_eq!(result, "1970-01-01T00:00:00Z");
+
+    let datetime = OffsetDateTime::from_unix_timestamp(1_000_000_000).unwrap();
+    let result = to_iso8601_datetime(&datetime);
+    assert_eq!(result, "2001-09-09T01:46:40Z");
+  }
+
+  #[test]
+  fn datetimes_are_converted_to_utc() {
+    let offset = time::UtcOffset::from_hms(-8, 0, 0).unwrap();
+    let datetime = OffsetDateTime::from_unix_timestamp(1_000_000_000)
+      .unwrap()
+      .to_offset(offset);
+    assert_eq!(to_iso8601_datetime(&datetime), "2001-09-09T01:46:40Z");
+    assert_eq!(to_long_datetime(&datetime), "20010909T014640Z");
+    assert_eq!(to_short_datetime(&datetime), "20010909");
+    assert_eq!(to_http_date(&datetime), "Sun, 09 Sep 2001 01:46:40 GMT");
+  }
+
+  #[test]
+  fn to_http_date_works() {
+    let datetime = OffsetDateTime::from_unix_timestamp(1175023002).unwrap();
+    let result = to_http_date(&datetime);
+    assert_eq!(result, "Tue, 27 Mar 2007 19:16:42 GMT");
+  }
+
   #[test]
   fn uri_encode_works() {
     let result = uri_encode("foo", false);
@@ -3,10 +3,13 @@
 //! Crypto goes here.
 //!
 use crate::aws_format::{query_params_string, security_token_string, to_short_datetime};
+use crate::prelude::*;
+use crate::{SecretKey, SigningKey};
+use alloc::borrow::Cow;
 use hmac::{Hmac, Mac};
 use sha2::{Digest, Sha256};
-use std::borrow::Cow;
 use time::OffsetDateTime;
+use zeroize::Zeroize;
 
 // Create alias for HMAC-SHA256
 // @see https://docs.rs/hmac/latest/hmac/
@@ -67,29 +70,35 @@ fn fold_hmacs(items: &[&[u8]]) -> Vec<u8> {
 /// # Examples
 ///
 /// ```
-/// use common_s3_headers::aws_math::get_signature_key;
+/// use common_s3_headers::{aws_math::get_signature_key, SecretKey};
 /// use time::OffsetDateTime;
 ///
-/// let datetime = OffsetDateTime::now_utc();
-/// let result = get_signature_key(&datetime, "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY", "us-east-1", "iam");
-/// assert_eq!(result, vec![
-///  74, 3, 90, 110, 208, 212, 221, 157, 23, 92, 98, 135, 89, 69, 167, 237, 78, 139, 55, 231, 149, 132, 41, 187, 21, 242, 192, 178, 126, 53, 92, 48
+/// // 20150830T000000Z
+/// let datetime = OffsetDateTime::from_unix_timestamp(1440892800).unwrap();
+/// let secret_key = SecretKey::new("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY");
+/// let result = get_signature_key(&datetime, &secret_key, "us-east-1", "iam");
+/// assert_eq!(result.expose_bytes(), &[
+///  196, 175, 177, 204, 87, 113, 216, 113, 118, 58, 57, 62, 68, 183, 3, 87, 27, 85, 204, 40, 66, 77, 26, 94, 134, 218, 110, 211, 193, 84, 164, 185
 /// ]);
 /// ```
 ///
 /// @see https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html
 /// @see https://docs.aws.amazon.com/translate/latest/dg/examples-sigv4.html
-pub fn get_signature_key(datetime: &OffsetDateTime, secret_key: &str, region: &str, service: &str) -> Vec<u8> {
-  let secret = format!("AWS4{}", secret_key);
+pub fn get_signature_key(datetime: &OffsetDateTime, secret_key: &SecretKey, region: &str, service: &str) -> SigningKey {
+  let mut secret = format!("AWS4{}", secret_key.expose_secret());
   let formatted_datetime = to_short_datetime(datetime);
 
-  fold_hmacs(&[
+  let mut bytes = fold_hmacs(&[
     secret.as_bytes(),
     formatted_datetime.as_bytes(),
     region.as_bytes(),
     service.as_bytes(),
     b"aws4_request",
-  ])
+  ]);
+  let signing_key = SigningKey::from(<[u8; 32]>::try_from(bytes.as_slice()).expect("SHA-256 is 32 bytes"));
+  secret.zeroize();
+  bytes.zeroize();
+  signing_key
 }
 
 /// Gets the authorization header for AWS.
@@ -161,14 +170,14 @@ mod tests {
       .with_hms(0, 0, 0)
       .unwrap()
       .assume_utc();
-    let secret_key = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+    let secret_key = SecretKey::new("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY");
     let region = "us-east-1";
     let service = "iam";
 
-    let result = get_signature_key(datetime, secret_key, region, service);
+    let result = get_signature_key(datetime, &secret_key, region, service);
 
     assert::equal_hex_bytes(
-      &result,
+      result.expose_bytes(),
       "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9",
     );
   }
@@ -214,9 +223,9 @@ mod tests {
     assert_eq!(EXPECTED_STRING_TO_SIGN, string_to_sign);
 
     let expected = "f0e8bdb87c964420e857bd35b5d6ed310bd44f0170aba48dd91039c6036bdb41";
-    let secret = "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY";
-    let signing_key = get_signature_key(&datetime, secret, "us-east-1", "s3");
-    let mut hmac = Hmac::<Sha256>::new_from_slice(&signing_key).unwrap();
+    let secret = SecretKey::new("wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY");
+    let signing_key = get_signature_key(&datetime, &secret, "us-east-1", "s3");
+    let mut hmac = Hmac::<Sha256>::new_from_slice(signing_key.expose_bytes()).unwrap();
     hmac.update(string_to_sign.as_bytes());
     assert_eq!(expected, hex::encode(hmac.finalize().into_bytes()));
   }
@@ -0,0 +1,367 @@
+//! Signs an S3 request and prints the signed headers, a `curl` command or a
+//! presigned URL. For debugging authentication problems.
+//!
+//! Credentials come from `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`, or
+//! from a profile in `~/.aws/credentials`. Run `s3-sign --help` for usage.
+//!
+use common_s3_headers::{
+  aws_canonical::HeaderPolicy, aws_format, get_presigned_url_with_trace, get_signed_headers_with_trace, Credentials,
+  S3DateTime, S3HeadersBuilder, SigningTrace, UNSIGNED_PAYLOAD,
+};
+use std::{collections::BTreeMap, env, fs, path::PathBuf, process::ExitCode};
+use url::Url;
+
+const USAGE: &str = "Usage: s3-sign [OPTIONS] <URL>
+
+Signs an S3 request with AWS Signature Version 4.
+
+Options:
+  -X, --method <METHOD>       HTTP method [default: GET]
+  -H, --header <NAME:VALUE>   Extra header to sign and send; repeatable
+      --body <FILE>           Sign the SHA-256 of this file as the payload
+      --unsigned-payload      Sign UNSIGNED-PAYLOAD instead of the body
+      --region <REGION>       [default: AWS_REGION, the profile, or us-east-1]
+      --service <SERVICE>     [default: s3]
+      --profile <NAME>        Read credentials and region from this profile
+      --date <DATETIME>       Sign at this time, as YYYYMMDDTHHMMSSZ
+      --sign-all-headers      Sign every header, not only host and x-amz-*
+      --curl                  Print a curl command instead of the headers
+      --presign <SECONDS>     Print a presigned URL valid for this long
+      --trace                 Print the canonical request and string to sign
+                              to stderr
+  -h, --help                  Print this help
+";
+
+/// The longest a presigned URL can be valid for.
+const MAX_PRESIGN_SECONDS: u32 = 604_800;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+  Headers,
+  Curl,
+  Presign(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Args {
+  method: String,
+  url: String,
+  headers: Vec<(String, String)>,
+  body: Option<PathBuf>,
+  unsigned_payload: bool,
+  region: Option<String>,
+  service: String,
+  profile: Option<String>,
+  date: Option<String>,
+  sign_all_headers: bool,
+  output: Output,
+  trace: bool,
+}
+
+/// `Ok(None)` means help was asked for.
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Args>, String> {
+  let mut args = args.into_iter();
+  let mut result = Args {
+    method: "GET".to_owned(),
+    url: String::new(),
+    headers: Vec::new(),
+    body: None,
+    unsigned_payload: false,
+    region: None,
+    service: "s3".to_owned(),
+    profile: None,
+    date: None,
+    sign_all_headers: false,
+    output: Output::Headers,
+    trace: false,
+  };
+  let mut url = None;
+
+  while let Some(arg) = args.next() {
+    let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+    match arg.as_str() {
+      "-h" | "--help" => return Ok(None),
+      "-X" | "--method" => result.method = value(&arg)?.to_ascii_uppercase(),
+      "-H" | "--header" => {
+        let header = value(&arg)?;
+        let (name, value) = header
+          .split_once(':')
+          .ok_or_else(|| format!("header {:?} must be NAME:VALUE", header))?;
+        result.headers.push((name.trim().to_owned(), value.trim().to_owned()));
+      }
+      "--body" => result.body = Some(PathBuf::from(value(&arg)?)),
+      "--unsigned-payload" => result.unsigned_payload = true,
+      "--region" => result.region = Some(value(&arg)?),
+      "--service" => result.service = value(&arg)?,
+      "--profile" => result.profile = Some(value(&arg)?),
+      "--date" => result.date = Some(value(&arg)?),
+      "--sign-all-headers" => result.sign_all_headers = true,
+      "--curl" => result.output = Output::Curl,
+      "--presign" => {
+        let seconds = value(&arg)?;
+        let seconds = seconds
+          .parse()
+          .ok()
+          .filter(|seconds| (1..=MAX_PRESIGN_SECONDS).contains(seconds))
+          .ok_or_else(|| format!("--presign must be 1 to {} seconds", MAX_PRESIGN_SECONDS))?;
+        result.output = Output::Presign(seconds);
+      }
+      "--trace" => result.trace = true,
+      _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+      _ if url.is_none() => url = Some(arg),
+      _ => return Err(format!("unexpected argument {}", arg)),
+    }
+  }
+
+  result.url = url.ok_or("missing URL")?;
+  Ok(Some(result))
+}
+
+/// Reads an AWS config or credentials file into sections of keys and values.
+fn parse_ini(text: &str) -> BTreeMap<String, BTreeMap<String, String>> {
+  let mut sections: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+  let mut section = String::new();
+  for line in text.lines() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+      continue;
+    }
+    if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+      section = name.trim().to_owned();
+    } else if let Some((key, value)) = line.split_once('=') {
+      sections
+        .entry(section.clone())
+        .or_default()
+        .insert(key.trim().to_owned(), value.trim().to_owned());
+    }
+  }
+  sections
+}
+
+fn get_aws_file(env_name: &str, file_name: &str) -> Option<BTreeMap<String, BTreeMap<String, String>>> {
+  let path = match env::var_os(env_name) {
+    Some(path) => PathBuf::from(path),
+    None => PathBuf::from(env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?)
+      .join(".aws")
+      .join(file_name),
+  };
+  fs::read_to_string(path).ok().map(|text| parse_ini(&text))
+}
+
+/// The environment wins, unless a profile was chosen.
+fn get_credentials(profile: Option<&str>) -> Result<Credentials, String> {
+  if profile.is_none() {
+    if let (Ok(access_key), Ok(secret_key)) = (env::var("AWS_ACCESS_KEY_ID"), env::var("AWS_SECRET_ACCESS_KEY")) {
+      let mut credentials = Credentials::new(access_key, secret_key);
+      if let Ok(token) = env::var("AWS_SESSION_TOKEN") {
+        credentials = credentials.set_session_token(token);
+      }
+      return Ok(credentials);
+    }
+  }
+
+  let profile = get_profile_name(profile);
+  let files = get_aws_file("AWS_SHARED_CREDENTIALS_FILE", "credentials")
+    .ok_or("no credentials: set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY, or create ~/.aws/credentials")?;
+  let section = files
+    .get(&profile)
+    .ok_or_else(|| format!("profile {:?} not found in the credentials file", profile))?;
+  let (Some(access_key), Some(secret_key)) = (section.get("aws_access_key_id"), section.get("aws_secret_access_key"))
+  else {
+    return Err(format!("profile {:?} has no access key or secret key", profile));
+  };
+  let mut credentials = Credentials::new(access_key, secret_key);
+  if let Some(token) = section.get("aws_session_token") {
+    credentials = credentials.set_session_token(token);
+  }
+  Ok(credentials)
+}
+
+fn get_profile_name(profile: Option<&str>) -> String {
+  profile
+    .map(str::to_owned)
+    .or_else(|| env::var("AWS_PROFILE").ok())
+    .unwrap_or_else(|| "default".to_owned())
+}
+
+fn get_region(args: &Args) -> String {
+  if let Some(region) = &args.region {
+    return region.clone();
+  }
+  if let Some(region) = ["AWS_REGION", "AWS_DEFAULT_REGION"]
+    .iter()
+    .find_map(|name| env::var(name).ok().filter(|region| !region.is_empty()))
+  {
+    return region;
+  }
+  let profile = get_profile_name(args.profile.as_deref());
+  let section = match profile.as_str() {
+    "default" => profile.clone(),
+    _ => format!("profile {}", profile),
+  };
+  get_aws_file("AWS_CONFIG_FILE", "config")
+    .and_then(|config| config.get(&section)?.get("region").cloned())
+    .unwrap_or_else(|| "us-east-1".to_owned())
+}
+
+/// Quotes a value for a POSIX shell.
+fn shell_quote(value: &str) -> String {
+  format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn print_trace(trace: &SigningTrace) {
+  eprintln!("Canonical request:\n{}\n", trace.canonical_request);
+  eprintln!("String to sign:\n{}\n", trace.string_to_sign);
+  eprintln!("Signed headers: {}", trace.signed_headers);
+  eprintln!("Signature: {}\n", trace.signature);
+}
+
+fn run(args: Args) -> Result<(), String> {
+  let url = Url::parse(&args.url).map_err(|error| format!("invalid URL {:?}: {}", args.url, error))?;
+  let credentials = get_credentials(args.profile.as_deref())?;
+  let region = get_region(&args);
+  let datetime = match &args.date {
+    Some(date) => S3DateTime::UnixTimestamp(
+      aws_format::parse_long_datetime(date)
+        .ok_or_else(|| format!("--date {:?} must be YYYYMMDDTHHMMSSZ", date))?
+        .unix_timestamp(),
+    ),
+    None => S3DateTime::Now,
+  };
+  let body = match &args.body {
+    Some(path) => Some(fs::read(path).map_err(|error| format!("can't read {}: {}", path.display(), error))?),
+    None => None,
+  };
+
+  let mut builder = S3HeadersBuilder::new(&url)
+    .set_access_key(&credentials.access_key)
+    .set_secret_key(&credentials.secret_key)
+    .set_region(&region)
+    .set_datetime(datetime)
+    .set_method(&args.method)
+    .set_service(&args.service);
+  if args.sign_all_headers {
+    builder = builder.set_header_policy(HeaderPolicy::All);
+  }
+  for (name, value) in &args.headers {
+    builder = builder.set_header(name.clone(), value.clone());
+  }
+  if let Some((name, value)) = credentials.to_session_token_header() {
+    builder = builder.set_header(name, value);
+  }
+  builder = match &body {
+    _ if args.unsigned_payload => builder.set_payload_hash(UNSIGNED_PAYLOAD),
+    Some(body) => builder.set_payload_hash_with_content(body),
+    None => builder,
+  };
+
+  if let Output::Presign(seconds) = args.output {
+    let (presigned_url, trace) = get_presigned_url_with_trace(builder, seconds);
+    if args.trace {
+      print_trace(&trace);
+    }
+    println!("{}", presigned_url);
+    return Ok(());
+  }
+
+  let (headers, trace) = get_signed_headers_with_trace(builder);
+  if args.trace {
+    print_trace(&trace);
+  }
+  if args.output == Output::Curl {
+    let mut command = format!("curl -X {} {}", args.method, shell_quote(url.as_str()));
+    for (name, value) in headers.iter() {
+      command.push_str(&format!(" \\\n  -H {}", shell_quote(&format!("{}: {}", name, value))));
+    }
+    if let Some(path) = &args.body {
+      command.push_str(&format!(
+        " \\\n  --data-binary {}",
+        shell_quote(&format!("@{}", path.display()))
+      ));
+    }
+    println!("{}", command);
+  } else {
+    for (name, value) in headers.iter() {
+      println!("{}: {}", name, value);
+    }
+  }
+  Ok(())
+}
+
+fn main() -> ExitCode {
+  match parse_args(env::args().skip(1)) {
+    Ok(Some(args)) => match run(args) {
+      Ok(()) => ExitCode::SUCCESS,
+      Err(error) => {
+        eprintln!("s3-sign: {}", error);
+        ExitCode::FAILURE
+      }
+    },
+    Ok(None) => {
+      print!("{}", USAGE);
+      ExitCode::SUCCESS
+    }
+    Err(error) => {
+      eprintln!("s3-sign: {}\n\n{}", error, USAGE);
+      ExitCode::from(2)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common_testing::assert;
+
+  fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+    parse_args(args.iter().map(|arg| arg.to_string()))
+  }
+
+  #[test]
+  fn parse_args_reads_options() {
+    let args = parse(&[
+      "-X",
+      "put",
+      "-H",
+      "Content-Type: application/json",
+      "--presign",
+      "3600",
+      "--trace",
+      "https://jsonlog.s3.amazonaws.com/test.json",
+    ])
+    .unwrap()
+    .unwrap();
+
+    assert::equal(args.method.as_str(), "PUT");
+    assert::equal(
+      args.headers,
+      vec![("Content-Type".to_owned(), "application/json".to_owned())],
+    );
+    assert::equal(args.output, Output::Presign(3600));
+    assert!(args.trace);
+    assert::equal(args.url.as_str(), "https://jsonlog.s3.amazonaws.com/test.json");
+  }
+
+  #[test]
+  fn parse_args_rejects_bad_input() {
+    assert::err(&parse(&[]));
+    assert::err(&parse(&["--presign", "604801", "https://a"]));
+    assert::err(&parse(&["-H", "no-colon", "https://a"]));
+    assert::err(&parse(&["--nope", "https://a"]));
+    assert::equal(parse(&["--help"]), Ok(None));
+  }
+
+  #[test]
+  fn parse_ini_reads_sections() {
+    let sections =
+      parse_ini("# comment\n[default]\naws_access_key_id = AKID\n\n[profile dev]\nregion=eu-west-1\n; other\n");
+
+    assert::equal(sections["default"]["aws_access_key_id"].as_str(), "AKID");
+    assert::equal(sections["profile dev"]["region"].as_str(), "eu-west-1");
+  }
+
+  #[test]
+  fn shell_quote_escapes_single_quotes() {
+    assert::equal(shell_quote("it's").as_str(), "'it'\\''s'");
+  }
+}
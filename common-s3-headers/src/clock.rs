@@ -0,0 +1,236 @@
+//! Clocks for signing, and correcting for clock skew.
+//!
+//! S3 rejects requests signed more than 15 minutes away from its own time
+//! with `RequestTimeTooSkewed`. An `OffsetClock` can be corrected from the
+//! server time in that error, or from a `Date` response header, so later
+//! signatures use the server's idea of now.
+//!
+use crate::{aws_format, S3DateTime};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::sync::{
+  atomic::{AtomicI64, Ordering},
+  Arc,
+};
+#[cfg(feature = "std")]
+use time::Duration;
+use time::OffsetDateTime;
+
+/// A source of the current time for signing.
+pub trait Clock: fmt::Debug + Send + Sync {
+  fn now(&self) -> OffsetDateTime;
+
+  /// Gets the current time as a fixed `S3DateTime` for a builder.
+  fn to_s3_datetime(&self) -> S3DateTime {
+    S3DateTime::UnixTimestamp(self.now().unix_timestamp())
+  }
+}
+
+/// The system clock, in UTC. Needs the `std` feature.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+  fn now(&self) -> OffsetDateTime {
+    OffsetDateTime::now_utc()
+  }
+}
+
+/// A clock that is always at the same time. Useful for tests.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{Clock, FixedClock};
+///
+/// let clock = FixedClock::from_unix_timestamp(1_000_000_000);
+/// assert_eq!(clock.now().unix_timestamp(), 1_000_000_000);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub OffsetDateTime);
+
+impl FixedClock {
+  pub fn new(datetime: OffsetDateTime) -> Self {
+    Self(datetime)
+  }
+
+  /// # Panics
+  ///
+  /// If the timestamp is out of range for `time::OffsetDateTime`.
+  pub fn from_unix_timestamp(timestamp: i64) -> Self {
+    Self(OffsetDateTime::from_unix_timestamp(timestamp).expect("Timestamp in range"))
+  }
+}
+
+impl Clock for FixedClock {
+  fn now(&self) -> OffsetDateTime {
+    self.0
+  }
+}
+
+/// The system clock plus an offset in seconds. Clones share the offset, so
+/// correcting one corrects them all. Needs the `std` feature.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{Clock, OffsetClock};
+///
+/// let clock = OffsetClock::new();
+/// let shared = clock.clone();
+///
+/// let body = "<Error><Code>RequestTimeTooSkewed</Code>\
+///   <RequestTime>20010909T014640Z</RequestTime>\
+///   <ServerTime>2001-09-09T02:46:40Z</ServerTime>\
+///   <MaxAllowedSkewMilliseconds>900000</MaxAllowedSkewMilliseconds></Error>";
+/// assert!(clock.correct_from_error_body(body));
+///
+/// // Our clock is far ahead of 2001, so the offset is very negative.
+/// assert!(shared.offset_seconds() < 0);
+/// ```
+///
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct OffsetClock {
+  offset: Arc<AtomicI64>,
+}
+
+#[cfg(feature = "std")]
+impl OffsetClock {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn offset_seconds(&self) -> i64 {
+    self.offset.load(Ordering::Relaxed)
+  }
+
+  pub fn set_offset_seconds(&self, seconds: i64) {
+    self.offset.store(seconds, Ordering::Relaxed);
+  }
+
+  /// Sets the offset so that now is the given server time.
+  pub fn correct_from_server_time(&self, server_time: OffsetDateTime) {
+    self.set_offset_seconds(get_clock_skew(server_time, OffsetDateTime::now_utc()));
+  }
+
+  /// Corrects from the `ServerTime` of a `RequestTimeTooSkewed` error
+  /// document. Returns false, and changes nothing, for any other body.
+  pub fn correct_from_error_body(&self, body: &str) -> bool {
+    match get_server_time_from_error(body) {
+      Some(server_time) => {
+        self.correct_from_server_time(server_time);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Corrects from the value of a `Date` response header. Returns false, and
+  /// changes nothing, if it isn't an HTTP date.
+  pub fn correct_from_date_header(&self, value: &str) -> bool {
+    match aws_format::parse_http_date(value) {
+      Some(server_time) => {
+        self.correct_from_server_time(server_time);
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl Clock for OffsetClock {
+  fn now(&self) -> OffsetDateTime {
+    OffsetDateTime::now_utc() + Duration::seconds(self.offset_seconds())
+  }
+}
+
+/// Gets the seconds to add to the local time to match the server time.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::get_clock_skew;
+/// use time::OffsetDateTime;
+///
+/// let server = OffsetDateTime::from_unix_timestamp(1_000_000_000).unwrap();
+/// let local = OffsetDateTime::from_unix_timestamp(1_000_001_200).unwrap();
+/// assert_eq!(get_clock_skew(server, local), -1200);
+/// ```
+///
+pub fn get_clock_skew(server_time: OffsetDateTime, local_time: OffsetDateTime) -> i64 {
+  (server_time - local_time).whole_seconds()
+}
+
+/// Gets the `ServerTime` from a `RequestTimeTooSkewed` error document.
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/API/ErrorResponses.html
+///
+pub fn get_server_time_from_error(body: &str) -> Option<OffsetDateTime> {
+  if aws_format::get_element_text(body, "Code")? != "RequestTimeTooSkewed" {
+    return None;
+  }
+  aws_format::parse_iso8601_datetime(aws_format::get_element_text(body, "ServerTime")?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common_testing::assert;
+
+  const SKEWED_BODY: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+    <Error><Code>RequestTimeTooSkewed</Code>\
+    <Message>The difference between the request time and the current time is too large.</Message>\
+    <RequestTime>20010909T014640Z</RequestTime>\
+    <ServerTime>2001-09-09T02:06:40Z</ServerTime>\
+    <MaxAllowedSkewMilliseconds>900000</MaxAllowedSkewMilliseconds>\
+    <RequestId>4442587FB7D0A2F9</RequestId></Error>";
+
+  #[test]
+  fn get_server_time_from_error_reads_server_time() {
+    let result = get_server_time_from_error(SKEWED_BODY).unwrap();
+    assert::equal(result.unix_timestamp(), 1_000_001_200);
+  }
+
+  #[test]
+  fn get_server_time_from_error_ignores_other_errors() {
+    let body = "<Error><Code>AccessDenied</Code><ServerTime>2001-09-09T02:06:40Z</ServerTime></Error>";
+    assert::none(&get_server_time_from_error(body));
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn offset_clock_shares_correction() {
+    let clock = OffsetClock::new();
+    let shared = clock.clone();
+    let server_time = OffsetDateTime::now_utc() + Duration::hours(1);
+    clock.correct_from_server_time(server_time);
+
+    // Allow for the clock ticking over between calls.
+    let skew = (shared.now() - server_time).whole_seconds();
+    assert!(skew.abs() <= 1);
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn offset_clock_rejects_bad_date_header() {
+    let clock = OffsetClock::new();
+    clock.set_offset_seconds(30);
+    assert!(!clock.correct_from_date_header("yesterday"));
+    assert::equal(clock.offset_seconds(), 30);
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn offset_clock_corrects_from_date_header() {
+    let clock = OffsetClock::new();
+    assert!(clock.correct_from_date_header("Sun, 09 Sep 2001 01:46:40 GMT"));
+    assert!(clock.offset_seconds() < 0);
+  }
+}
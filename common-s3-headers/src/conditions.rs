@@ -0,0 +1,162 @@
+use crate::aws_format;
+use crate::prelude::*;
+use time::OffsetDateTime;
+
+/// Conditional request headers, for optimistic concurrency. A failed
+/// condition returns 412 Precondition Failed, or 304 Not Modified for reads.
+///
+/// ETags are sent as given, so pass them as S3 returned them, with quotes.
+///
+/// The `If-*` headers are only signed with `HeaderPolicy::All`. The copy
+/// source headers start with "x-amz-" and are always signed.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::Conditions;
+///
+/// // Only create the object if it doesn't exist yet.
+/// let headers = Conditions::new().set_if_none_match_any().to_headers();
+/// assert_eq!(headers, vec![("If-None-Match", "*".to_owned())]);
+///
+/// // Only overwrite the version we read.
+/// let headers = Conditions::new().set_if_match("\"9b2cf535f27731c974343645a3985328\"").to_headers();
+/// assert_eq!(headers, vec![("If-Match", "\"9b2cf535f27731c974343645a3985328\"".to_owned())]);
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/userguide/conditional-requests.html
+/// * https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html#API_CopyObject_RequestSyntax
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Conditions {
+  pub if_match: Option<String>,
+  pub if_none_match: Option<String>,
+  pub if_modified_since: Option<OffsetDateTime>,
+  pub if_unmodified_since: Option<OffsetDateTime>,
+}
+
+impl Conditions {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn set_if_match<T: Into<String>>(mut self, etag: T) -> Self {
+    self.if_match = Some(etag.into());
+    self
+  }
+
+  pub fn set_if_none_match<T: Into<String>>(mut self, etag: T) -> Self {
+    self.if_none_match = Some(etag.into());
+    self
+  }
+
+  /// `If-None-Match: *`, so a PUT only succeeds if the key doesn't exist.
+  pub fn set_if_none_match_any(self) -> Self {
+    self.set_if_none_match("*")
+  }
+
+  pub fn set_if_modified_since(mut self, value: OffsetDateTime) -> Self {
+    self.if_modified_since = Some(value);
+    self
+  }
+
+  pub fn set_if_unmodified_since(mut self, value: OffsetDateTime) -> Self {
+    self.if_unmodified_since = Some(value);
+    self
+  }
+
+  pub fn is_empty(&self) -> bool {
+    *self == Self::default()
+  }
+
+  /// Gets the headers for the object being read or written.
+  pub fn to_headers(&self) -> Vec<(&'static str, String)> {
+    self.to_headers_with_names(["If-Match", "If-None-Match", "If-Modified-Since", "If-Unmodified-Since"])
+  }
+
+  /// Gets the headers for the source object of a copy.
+  pub fn to_copy_source_headers(&self) -> Vec<(&'static str, String)> {
+    self.to_headers_with_names([
+      "x-amz-copy-source-if-match",
+      "x-amz-copy-source-if-none-match",
+      "x-amz-copy-source-if-modified-since",
+      "x-amz-copy-source-if-unmodified-since",
+    ])
+  }
+
+  fn to_headers_with_names(&self, names: [&'static str; 4]) -> Vec<(&'static str, String)> {
+    let [if_match, if_none_match, if_modified_since, if_unmodified_since] = names;
+    let mut headers = Vec::new();
+    if let Some(etag) = &self.if_match {
+      headers.push((if_match, etag.clone()));
+    }
+    if let Some(etag) = &self.if_none_match {
+      headers.push((if_none_match, etag.clone()));
+    }
+    if let Some(datetime) = &self.if_modified_since {
+      headers.push((if_modified_since, aws_format::to_http_date(datetime)));
+    }
+    if let Some(datetime) = &self.if_unmodified_since {
+      headers.push((if_unmodified_since, aws_format::to_http_date(datetime)));
+    }
+    headers
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common_testing::assert;
+
+  #[test]
+  fn to_headers_formats_http_dates() {
+    let datetime = OffsetDateTime::from_unix_timestamp(1_000_000_000).unwrap();
+    let result = Conditions::new()
+      .set_if_modified_since(datetime)
+      .set_if_unmodified_since(datetime)
+      .to_headers();
+
+    assert::equal(
+      result,
+      vec![
+        ("If-Modified-Since", "Sun, 09 Sep 2001 01:46:40 GMT".to_owned()),
+        ("If-Unmodified-Since", "Sun, 09 Sep 2001 01:46:40 GMT".to_owned()),
+      ],
+    );
+  }
+
+  #[test]
+  fn to_copy_source_headers_uses_copy_names() {
+    let datetime = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let result = Conditions::new()
+      .set_if_match("\"a\"")
+      .set_if_none_match("\"b\"")
+      .set_if_modified_since(datetime)
+      .set_if_unmodified_since(datetime)
+      .to_copy_source_headers();
+
+    assert::equal(
+      result,
+      vec![
+        ("x-amz-copy-source-if-match", "\"a\"".to_owned()),
+        ("x-amz-copy-source-if-none-match", "\"b\"".to_owned()),
+        (
+          "x-amz-copy-source-if-modified-since",
+          "Thu, 01 Jan 1970 00:00:00 GMT".to_owned(),
+        ),
+        (
+          "x-amz-copy-source-if-unmodified-since",
+          "Thu, 01 Jan 1970 00:00:00 GMT".to_owned(),
+        ),
+      ],
+    );
+  }
+
+  #[test]
+  fn is_empty_without_conditions() {
+    assert!(Conditions::new().is_empty());
+    assert!(!Conditions::new().set_if_none_match_any().is_empty());
+  }
+}
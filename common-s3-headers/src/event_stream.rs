@@ -0,0 +1,664 @@
+//! The `application/vnd.amazon.eventstream` framing used by SelectObjectContent
+//! responses, and the typed S3 Select events carried in it.
+//!
+//! Each message is a 12-byte prelude (total length, headers length and a CRC32
+//! of those eight bytes), typed headers, a payload and a CRC32 of everything
+//! before it.
+//!
+use crate::aws_format;
+use crate::prelude::*;
+use core::fmt;
+
+const PRELUDE_LENGTH: usize = 12;
+const CRC_LENGTH: usize = 4;
+/// The prelude and the trailing message CRC.
+const MIN_MESSAGE_LENGTH: usize = PRELUDE_LENGTH + CRC_LENGTH;
+/// The largest message S3 sends is far smaller; this bounds what a corrupt
+/// prelude can make the decoder wait for.
+pub const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+
+const CRC32_TABLE: [u32; 256] = {
+  let mut table = [0u32; 256];
+  let mut i = 0;
+  while i < 256 {
+    let mut crc = i as u32;
+    let mut bit = 0;
+    while bit < 8 {
+      crc = if crc & 1 == 1 {
+        (crc >> 1) ^ 0xEDB8_8320
+      } else {
+        crc >> 1
+      };
+      bit += 1;
+    }
+    table[i] = crc;
+    i += 1;
+  }
+  table
+};
+
+/// CRC-32 (IEEE), as used by event-stream preludes and messages.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+  !bytes.iter().fold(!0u32, |crc, &b| {
+    CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+  })
+}
+
+/// A typed event-stream header value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventStreamHeaderValue {
+  Bool(bool),
+  Byte(i8),
+  Short(i16),
+  Int(i32),
+  Long(i64),
+  Bytes(Vec<u8>),
+  String(String),
+  /// Milliseconds since the Unix epoch.
+  Timestamp(i64),
+  Uuid([u8; 16]),
+}
+
+impl EventStreamHeaderValue {
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      EventStreamHeaderValue::String(value) => Some(value),
+      _ => None,
+    }
+  }
+}
+
+impl From<&str> for EventStreamHeaderValue {
+  fn from(value: &str) -> Self {
+    EventStreamHeaderValue::String(value.to_owned())
+  }
+}
+
+impl From<String> for EventStreamHeaderValue {
+  fn from(value: String) -> Self {
+    EventStreamHeaderValue::String(value)
+  }
+}
+
+/// The message can't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventStreamError {
+  PreludeCrcMismatch {
+    expected: u32,
+    actual: u32,
+  },
+  MessageCrcMismatch {
+    expected: u32,
+    actual: u32,
+  },
+  InvalidLength(usize),
+  InvalidHeader(String),
+  /// The stream ended partway through a message.
+  Truncated,
+}
+
+impl fmt::Display for EventStreamError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      EventStreamError::PreludeCrcMismatch { expected, actual } => {
+        write!(
+          f,
+          "event-stream prelude CRC mismatch: expected {:08x}, got {:08x}",
+          expected, actual
+        )
+      }
+      EventStreamError::MessageCrcMismatch { expected, actual } => {
+        write!(
+          f,
+          "event-stream message CRC mismatch: expected {:08x}, got {:08x}",
+          expected, actual
+        )
+      }
+      EventStreamError::InvalidLength(length) => write!(f, "invalid event-stream message length: {}", length),
+      EventStreamError::InvalidHeader(reason) => write!(f, "invalid event-stream header: {}", reason),
+      EventStreamError::Truncated => write!(f, "event stream ended partway through a message"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EventStreamError {}
+
+/// One event-stream message.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{decode_event_stream_message, EventStreamMessage};
+///
+/// let message = EventStreamMessage::new()
+///   .set_header(":message-type", "event")
+///   .set_header(":event-type", "Cont")
+///   .set_payload(Vec::new());
+/// let bytes = message.encode();
+///
+/// let (decoded, used) = decode_event_stream_message(&bytes).unwrap().unwrap();
+/// assert_eq!(used, bytes.len());
+/// assert_eq!(decoded.header_str(":event-type"), Some("Cont"));
+/// ```
+///
+/// # See
+///
+/// - https://docs.aws.amazon.com/AmazonS3/latest/API/RESTSelectObjectAppendix.html
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventStreamMessage {
+  pub headers: Vec<(String, EventStreamHeaderValue)>,
+  pub payload: Vec<u8>,
+}
+
+impl EventStreamMessage {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn set_header<K: Into<String>, V: Into<EventStreamHeaderValue>>(mut self, name: K, value: V) -> Self {
+    self.headers.push((name.into(), value.into()));
+    self
+  }
+
+  pub fn set_payload<P: Into<Vec<u8>>>(mut self, payload: P) -> Self {
+    self.payload = payload.into();
+    self
+  }
+
+  pub fn header(&self, name: &str) -> Option<&EventStreamHeaderValue> {
+    self.headers.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+  }
+
+  pub fn header_str(&self, name: &str) -> Option<&str> {
+    self.header(name).and_then(EventStreamHeaderValue::as_str)
+  }
+
+  /// Gets the message as bytes, with both CRCs.
+  pub fn encode(&self) -> Vec<u8> {
+    let mut headers = Vec::new();
+    for (name, value) in &self.headers {
+      headers.push(name.len() as u8);
+      headers.extend_from_slice(name.as_bytes());
+      match value {
+        EventStreamHeaderValue::Bool(true) => headers.push(0),
+        EventStreamHeaderValue::Bool(false) => headers.push(1),
+        EventStreamHeaderValue::Byte(v) => {
+          headers.push(2);
+          headers.extend_from_slice(&v.to_be_bytes());
+        }
+        EventStreamHeaderValue::Short(v) => {
+          headers.push(3);
+          headers.extend_from_slice(&v.to_be_bytes());
+        }
+        EventStreamHeaderValue::Int(v) => {
+          headers.push(4);
+          headers.extend_from_slice(&v.to_be_bytes());
+        }
+        EventStreamHeaderValue::Long(v) => {
+          headers.push(5);
+          headers.extend_from_slice(&v.to_be_bytes());
+        }
+        EventStreamHeaderValue::Bytes(v) => {
+          headers.push(6);
+          headers.extend_from_slice(&(v.len() as u16).to_be_bytes());
+          headers.extend_from_slice(v);
+        }
+        EventStreamHeaderValue::String(v) => {
+          headers.push(7);
+          headers.extend_from_slice(&(v.len() as u16).to_be_bytes());
+          headers.extend_from_slice(v.as_bytes());
+        }
+        EventStreamHeaderValue::Timestamp(v) => {
+          headers.push(8);
+          headers.extend_from_slice(&v.to_be_bytes());
+        }
+        EventStreamHeaderValue::Uuid(v) => {
+          headers.push(9);
+          headers.extend_from_slice(v);
+        }
+      }
+    }
+
+    let total_length = MIN_MESSAGE_LENGTH + headers.len() + self.payload.len();
+    let mut bytes = Vec::with_capacity(total_length);
+    bytes.extend_from_slice(&(total_length as u32).to_be_bytes());
+    bytes.extend_from_slice(&(headers.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&crc32(&bytes).to_be_bytes());
+    bytes.extend_from_slice(&headers);
+    bytes.extend_from_slice(&self.payload);
+    bytes.extend_from_slice(&crc32(&bytes).to_be_bytes());
+    bytes
+  }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+  u32::from_be_bytes(bytes[..4].try_into().expect("Four bytes"))
+}
+
+/// Reads the first message in `bytes` and how many bytes it used, or `None`
+/// if `bytes` doesn't hold a whole message yet.
+pub fn decode_event_stream_message(bytes: &[u8]) -> Result<Option<(EventStreamMessage, usize)>, EventStreamError> {
+  if bytes.len() < PRELUDE_LENGTH {
+    return Ok(None);
+  }
+  let total_length = read_u32(&bytes[0..]) as usize;
+  let headers_length = read_u32(&bytes[4..]) as usize;
+  let expected = read_u32(&bytes[8..]);
+  let actual = crc32(&bytes[..8]);
+  if expected != actual {
+    return Err(EventStreamError::PreludeCrcMismatch { expected, actual });
+  }
+  if !(MIN_MESSAGE_LENGTH..=MAX_MESSAGE_LENGTH).contains(&total_length)
+    || headers_length > total_length - MIN_MESSAGE_LENGTH
+  {
+    return Err(EventStreamError::InvalidLength(total_length));
+  }
+  if bytes.len() < total_length {
+    return Ok(None);
+  }
+
+  let expected = read_u32(&bytes[total_length - CRC_LENGTH..]);
+  let actual = crc32(&bytes[..total_length - CRC_LENGTH]);
+  if expected != actual {
+    return Err(EventStreamError::MessageCrcMismatch { expected, actual });
+  }
+
+  let headers_end = PRELUDE_LENGTH + headers_length;
+  let message = EventStreamMessage {
+    headers: decode_headers(&bytes[PRELUDE_LENGTH..headers_end])?,
+    payload: bytes[headers_end..total_length - CRC_LENGTH].to_vec(),
+  };
+  Ok(Some((message, total_length)))
+}
+
+fn decode_headers(mut bytes: &[u8]) -> Result<Vec<(String, EventStreamHeaderValue)>, EventStreamError> {
+  fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8], EventStreamError> {
+    if bytes.len() < n {
+      return Err(EventStreamError::InvalidHeader(
+        "header runs past the headers section".to_owned(),
+      ));
+    }
+    let (head, tail) = bytes.split_at(n);
+    *bytes = tail;
+    Ok(head)
+  }
+  fn take_array<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], EventStreamError> {
+    Ok(take(bytes, N)?.try_into().expect("Exactly N bytes"))
+  }
+
+  let mut headers = Vec::new();
+  while !bytes.is_empty() {
+    let name_length = take(&mut bytes, 1)?[0] as usize;
+    let name = core::str::from_utf8(take(&mut bytes, name_length)?)
+      .map_err(|_| EventStreamError::InvalidHeader("name is not UTF-8".to_owned()))?
+      .to_owned();
+    let value = match take(&mut bytes, 1)?[0] {
+      0 => EventStreamHeaderValue::Bool(true),
+      1 => EventStreamHeaderValue::Bool(false),
+      2 => EventStreamHeaderValue::Byte(i8::from_be_bytes(take_array(&mut bytes)?)),
+      3 => EventStreamHeaderValue::Short(i16::from_be_bytes(take_array(&mut bytes)?)),
+      4 => EventStreamHeaderValue::Int(i32::from_be_bytes(take_array(&mut bytes)?)),
+      5 => EventStreamHeaderValue::Long(i64::from_be_bytes(take_array(&mut bytes)?)),
+      6 => {
+        let length = u16::from_be_bytes(take_array(&mut bytes)?) as usize;
+        EventStreamHeaderValue::Bytes(take(&mut bytes, length)?.to_vec())
+      }
+      7 => {
+        let length = u16::from_be_bytes(take_array(&mut bytes)?) as usize;
+        let value = core::str::from_utf8(take(&mut bytes, length)?)
+          .map_err(|_| EventStreamError::InvalidHeader(format!("value of {} is not UTF-8", name)))?;
+        EventStreamHeaderValue::String(value.to_owned())
+      }
+      8 => EventStreamHeaderValue::Timestamp(i64::from_be_bytes(take_array(&mut bytes)?)),
+      9 => EventStreamHeaderValue::Uuid(take_array(&mut bytes)?),
+      other => {
+        return Err(EventStreamError::InvalidHeader(format!(
+          "unknown type {} for {}",
+          other, name
+        )))
+      }
+    };
+    headers.push((name, value));
+  }
+  Ok(headers)
+}
+
+/// Reads messages from a response body as it arrives, in chunks of any size.
+#[derive(Debug, Clone, Default)]
+pub struct EventStreamDecoder {
+  buffer: Vec<u8>,
+}
+
+impl EventStreamDecoder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn push(&mut self, bytes: &[u8]) {
+    self.buffer.extend_from_slice(bytes);
+  }
+
+  /// Gets the next whole message, or `None` until more bytes are pushed.
+  pub fn next_message(&mut self) -> Result<Option<EventStreamMessage>, EventStreamError> {
+    Ok(decode_event_stream_message(&self.buffer)?.map(|(message, used)| {
+      self.buffer.drain(..used);
+      message
+    }))
+  }
+
+  /// Checks that the body didn't end partway through a message.
+  pub fn finish(self) -> Result<(), EventStreamError> {
+    if self.buffer.is_empty() {
+      Ok(())
+    } else {
+      Err(EventStreamError::Truncated)
+    }
+  }
+}
+
+/// The byte counts in `Stats` and `Progress` events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SelectStats {
+  pub bytes_scanned: u64,
+  pub bytes_processed: u64,
+  pub bytes_returned: u64,
+}
+
+impl SelectStats {
+  fn from_payload(payload: &[u8]) -> Self {
+    let body = core::str::from_utf8(payload).unwrap_or_default();
+    let get = |name| {
+      aws_format::get_element_text(body, name)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+    };
+    Self {
+      bytes_scanned: get("BytesScanned"),
+      bytes_processed: get("BytesProcessed"),
+      bytes_returned: get("BytesReturned"),
+    }
+  }
+}
+
+/// An event in a SelectObjectContent response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectEvent {
+  /// Some of the query result, in the requested output format. Records can
+  /// split anywhere, including inside a row.
+  Records(Vec<u8>),
+  Stats(SelectStats),
+  Progress(SelectStats),
+  /// Keeps the connection alive while S3 scans.
+  Cont,
+  /// The query finished. Without it, the result is incomplete.
+  End,
+  /// An event type this crate doesn't know yet.
+  Unknown {
+    event_type: String,
+    payload: Vec<u8>,
+  },
+}
+
+/// S3 reported an error partway through the response, or the response
+/// couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectEventError {
+  EventStream(EventStreamError),
+  Error {
+    code: String,
+    message: String,
+  },
+  InvalidMessage(String),
+  /// The response ended without an `End` event.
+  MissingEnd,
+}
+
+impl fmt::Display for SelectEventError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SelectEventError::EventStream(error) => error.fmt(f),
+      SelectEventError::Error { code, message } => write!(f, "S3 Select failed with {}: {}", code, message),
+      SelectEventError::InvalidMessage(reason) => write!(f, "invalid S3 Select message: {}", reason),
+      SelectEventError::MissingEnd => write!(f, "S3 Select response ended without an End event"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SelectEventError {}
+
+impl From<EventStreamError> for SelectEventError {
+  fn from(error: EventStreamError) -> Self {
+    SelectEventError::EventStream(error)
+  }
+}
+
+impl TryFrom<EventStreamMessage> for SelectEvent {
+  type Error = SelectEventError;
+
+  fn try_from(message: EventStreamMessage) -> Result<Self, Self::Error> {
+    match message.header_str(":message-type") {
+      Some("event") => {}
+      Some("error") => {
+        return Err(SelectEventError::Error {
+          code: message.header_str(":error-code").unwrap_or_default().to_owned(),
+          message: message.header_str(":error-message").unwrap_or_default().to_owned(),
+        })
+      }
+      other => {
+        return Err(SelectEventError::InvalidMessage(format!(
+          "unexpected :message-type {:?}",
+          other
+        )))
+      }
+    }
+    let event_type = message
+      .header_str(":event-type")
+      .ok_or_else(|| SelectEventError::InvalidMessage("missing :event-type".to_owned()))?;
+    Ok(match event_type {
+      "Records" => SelectEvent::Records(message.payload),
+      "Stats" => SelectEvent::Stats(SelectStats::from_payload(&message.payload)),
+      "Progress" => SelectEvent::Progress(SelectStats::from_payload(&message.payload)),
+      "Cont" => SelectEvent::Cont,
+      "End" => SelectEvent::End,
+      other => SelectEvent::Unknown {
+        event_type: other.to_owned(),
+        payload: message.payload,
+      },
+    })
+  }
+}
+
+/// Reads every event of a whole SelectObjectContent response body, checking
+/// that it ends with `End`.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{decode_select_events, EventStreamMessage, SelectEvent};
+///
+/// let event = |event_type: &str, payload: &[u8]| {
+///   EventStreamMessage::new()
+///     .set_header(":message-type", "event")
+///     .set_header(":event-type", event_type)
+///     .set_payload(payload)
+///     .encode()
+/// };
+/// let mut body = event("Records", b"a,1\nb,2\n");
+/// body.extend(event("End", b""));
+///
+/// let events = decode_select_events(&body).unwrap();
+/// assert_eq!(events, vec![SelectEvent::Records(b"a,1\nb,2\n".to_vec()), SelectEvent::End]);
+/// ```
+///
+/// # See
+///
+/// - https://docs.aws.amazon.com/AmazonS3/latest/API/API_SelectObjectContent.html#API_SelectObjectContent_ResponseSyntax
+///
+pub fn decode_select_events(body: &[u8]) -> Result<Vec<SelectEvent>, SelectEventError> {
+  let mut decoder = EventStreamDecoder::new();
+  decoder.push(body);
+  let mut events = Vec::new();
+  while let Some(message) = decoder.next_message()? {
+    events.push(SelectEvent::try_from(message)?);
+  }
+  decoder.finish()?;
+  match events.last() {
+    Some(SelectEvent::End) => Ok(events),
+    _ => Err(SelectEventError::MissingEnd),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common_testing::assert;
+
+  // From the awslabs event-stream test suite.
+  const EMPTY_MESSAGE: [u8; 16] = [
+    0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x05, 0xc2, 0x48, 0xeb, 0x7d, 0x98, 0xc8, 0xff,
+  ];
+
+  fn event(event_type: &str, payload: &[u8]) -> Vec<u8> {
+    EventStreamMessage::new()
+      .set_header(":message-type", "event")
+      .set_header(":event-type", event_type)
+      .set_payload(payload)
+      .encode()
+  }
+
+  #[test]
+  fn crc32_matches_check_value() {
+    assert::equal(crc32(b"123456789"), 0xCBF4_3926);
+  }
+
+  #[test]
+  fn encodes_known_empty_message() {
+    assert::equal(EventStreamMessage::new().encode(), EMPTY_MESSAGE.to_vec());
+    assert::equal(
+      decode_event_stream_message(&EMPTY_MESSAGE),
+      Ok(Some((EventStreamMessage::new(), 16))),
+    );
+  }
+
+  #[test]
+  fn round_trips_every_header_type() {
+    let message = EventStreamMessage::new()
+      .set_header("true", EventStreamHeaderValue::Bool(true))
+      .set_header("false", EventStreamHeaderValue::Bool(false))
+      .set_header("byte", EventStreamHeaderValue::Byte(-2))
+      .set_header("short", EventStreamHeaderValue::Short(-300))
+      .set_header("int", EventStreamHeaderValue::Int(70_000))
+      .set_header("long", EventStreamHeaderValue::Long(-5_000_000_000))
+      .set_header("bytes", EventStreamHeaderValue::Bytes(vec![1, 2, 3]))
+      .set_header("string", "value")
+      .set_header("timestamp", EventStreamHeaderValue::Timestamp(1_700_000_000_000))
+      .set_header("uuid", EventStreamHeaderValue::Uuid([9; 16]))
+      .set_payload(b"payload".to_vec());
+    let bytes = message.encode();
+    assert::equal(decode_event_stream_message(&bytes), Ok(Some((message, bytes.len()))));
+  }
+
+  #[test]
+  fn rejects_bad_crcs() {
+    let mut bytes = event("Cont", b"");
+    bytes[8] ^= 1;
+    assert::equal(
+      matches!(
+        decode_event_stream_message(&bytes),
+        Err(EventStreamError::PreludeCrcMismatch { .. })
+      ),
+      true,
+    );
+
+    let mut bytes = event("Records", b"abc");
+    let last = bytes.len() - 5;
+    bytes[last] ^= 1;
+    assert::equal(
+      matches!(
+        decode_event_stream_message(&bytes),
+        Err(EventStreamError::MessageCrcMismatch { .. })
+      ),
+      true,
+    );
+  }
+
+  #[test]
+  fn decoder_waits_for_whole_messages() {
+    let mut body = event("Records", b"a,1\n");
+    body.extend(event("End", b""));
+
+    let mut decoder = EventStreamDecoder::new();
+    let mut messages = Vec::new();
+    for chunk in body.chunks(5) {
+      decoder.push(chunk);
+      while let Some(message) = decoder.next_message().unwrap() {
+        messages.push(SelectEvent::try_from(message).unwrap());
+      }
+    }
+    assert::ok(&decoder.finish());
+    assert::equal(
+      messages,
+      vec![SelectEvent::Records(b"a,1\n".to_vec()), SelectEvent::End],
+    );
+  }
+
+  #[test]
+  fn decoder_reports_truncated_body() {
+    let body = event("Records", b"a,1\n");
+    let mut decoder = EventStreamDecoder::new();
+    decoder.push(&body[..body.len() - 1]);
+    assert::equal(decoder.next_message(), Ok(None));
+    assert::equal(decoder.finish(), Err(EventStreamError::Truncated));
+  }
+
+  #[test]
+  fn reads_stats_and_progress() {
+    let stats = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?><Stats><Details><BytesScanned>512</BytesScanned>\
+      <BytesProcessed>1024</BytesProcessed><BytesReturned>24</BytesReturned></Details></Stats>";
+    let progress = b"<Progress><Details><BytesScanned>10</BytesScanned></Details></Progress>";
+    let mut body = event("Progress", progress);
+    body.extend(event("Cont", b""));
+    body.extend(event("Stats", stats));
+    body.extend(event("End", b""));
+
+    assert::equal(
+      decode_select_events(&body),
+      Ok(vec![
+        SelectEvent::Progress(SelectStats {
+          bytes_scanned: 10,
+          ..Default::default()
+        }),
+        SelectEvent::Cont,
+        SelectEvent::Stats(SelectStats {
+          bytes_scanned: 512,
+          bytes_processed: 1024,
+          bytes_returned: 24,
+        }),
+        SelectEvent::End,
+      ]),
+    );
+  }
+
+  #[test]
+  fn reports_error_messages_and_missing_end() {
+    let error = EventStreamMessage::new()
+      .set_header(":message-type", "error")
+      .set_header(":error-code", "InvalidTextEncoding")
+      .set_header(":error-message", "UTF-8 encoding is required.")
+      .encode();
+    assert::equal(
+      decode_select_events(&error),
+      Err(SelectEventError::Error {
+        code: "InvalidTextEncoding".to_owned(),
+        message: "UTF-8 encoding is required.".to_owned(),
+      }),
+    );
+    assert::equal(
+      decode_select_events(&event("Records", b"a")),
+      Err(SelectEventError::MissingEnd),
+    );
+  }
+}
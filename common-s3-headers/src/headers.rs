@@ -0,0 +1,306 @@
+use crate::prelude::*;
+use alloc::borrow::Cow;
+use core::fmt;
+use zeroize::Zeroize;
+
+/// An owned list of headers with case-insensitive names. Names can be
+/// `'static` or built at runtime, such as `x-amz-meta-<name>`.
+///
+/// `insert` replaces any header with the same name. `append` keeps both,
+/// and repeated headers are signed as one comma-separated value.
+///
+/// SSE-C keys and session tokens are redacted in `Debug` and zeroed on drop.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::Headers;
+///
+/// let mut headers = Headers::new();
+/// headers.insert("Content-Type", "text/plain");
+/// headers.insert(format!("x-amz-meta-{}", "author"), "jane");
+/// headers.insert("content-type", "application/json");
+///
+/// assert_eq!(headers.len(), 2);
+/// assert_eq!(headers.get("CONTENT-TYPE"), Some("application/json"));
+/// assert_eq!(headers.get("x-amz-meta-author"), Some("jane"));
+///
+/// // Slices of `(&'static str, String)` still convert.
+/// let headers = Headers::from(&[("x-amz-storage-class", "STANDARD".to_owned())]);
+/// assert_eq!(headers.get("X-Amz-Storage-Class"), Some("STANDARD"));
+/// ```
+///
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct Headers {
+  entries: Vec<(Cow<'static, str>, String)>,
+}
+
+impl Headers {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_capacity(capacity: usize) -> Self {
+    Self {
+      entries: Vec::with_capacity(capacity),
+    }
+  }
+
+  /// Sets the header, replacing any with the same name. The header keeps the
+  /// position of the first one it replaces. Returns the old value.
+  pub fn insert<K: Into<Cow<'static, str>>, V: Into<String>>(&mut self, name: K, value: V) -> Option<String> {
+    let name = name.into();
+    let value = value.into();
+    match self.position(&name) {
+      Some(index) => {
+        let old = core::mem::replace(&mut self.entries[index], (name, value)).1;
+        let mut i = index + 1;
+        while i < self.entries.len() {
+          if self.entries[i].0.eq_ignore_ascii_case(&self.entries[index].0) {
+            self.entries.remove(i).1.zeroize();
+          } else {
+            i += 1;
+          }
+        }
+        Some(old)
+      }
+      None => {
+        self.entries.push((name, value));
+        None
+      }
+    }
+  }
+
+  /// Adds the header, keeping any with the same name.
+  pub fn append<K: Into<Cow<'static, str>>, V: Into<String>>(&mut self, name: K, value: V) {
+    self.entries.push((name.into(), value.into()));
+  }
+
+  /// Gets the first value of the header.
+  pub fn get(&self, name: &str) -> Option<&str> {
+    self.position(name).map(|index| self.entries[index].1.as_str())
+  }
+
+  /// Gets every value of the header, in order.
+  pub fn get_all<'h>(&'h self, name: &'h str) -> impl Iterator<Item = &'h str> + 'h {
+    self
+      .entries
+      .iter()
+      .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+      .map(|(_, v)| v.as_str())
+  }
+
+  pub fn contains_key(&self, name: &str) -> bool {
+    self.position(name).is_some()
+  }
+
+  /// Removes every value of the header. Returns the first.
+  pub fn remove(&mut self, name: &str) -> Option<String> {
+    let mut removed = None;
+    self.entries.retain_mut(|(k, v)| {
+      if k.eq_ignore_ascii_case(name) {
+        if removed.is_none() {
+          removed = Some(core::mem::take(v));
+        }
+        v.zeroize();
+        false
+      } else {
+        true
+      }
+    });
+    removed
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+    self.entries.iter().map(|(k, v)| (k.as_ref(), v.as_str()))
+  }
+
+  /// The headers as a slice, for functions that take `&[(K, V)]`.
+  pub fn as_slice(&self) -> &[(Cow<'static, str>, String)] {
+    &self.entries
+  }
+
+  /// Converts to a list of pairs, as `build` returns.
+  pub fn into_vec(mut self) -> Vec<(Cow<'static, str>, String)> {
+    core::mem::take(&mut self.entries)
+  }
+
+  /// Converts to the older `'static` header list, as `build` returns. Names
+  /// built at runtime are left out, and secret values among them are zeroed.
+  /// Use `into_vec` to keep them.
+  pub fn into_static_vec(mut self) -> Vec<(&'static str, String)> {
+    core::mem::take(&mut self.entries)
+      .into_iter()
+      .filter_map(|(k, mut v)| match k {
+        Cow::Borrowed(name) => Some((name, v)),
+        Cow::Owned(name) => {
+          if is_sensitive(&name) {
+            v.zeroize();
+          }
+          None
+        }
+      })
+      .collect()
+  }
+
+  fn position(&self, name: &str) -> Option<usize> {
+    self.entries.iter().position(|(k, _)| k.eq_ignore_ascii_case(name))
+  }
+}
+
+impl<K: Into<Cow<'static, str>>, V: Into<String>> FromIterator<(K, V)> for Headers {
+  fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+    let mut headers = Headers::new();
+    headers.extend(iter);
+    headers
+  }
+}
+
+/// Appends, so repeated headers are kept.
+impl<K: Into<Cow<'static, str>>, V: Into<String>> Extend<(K, V)> for Headers {
+  fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+    for (k, v) in iter {
+      self.append(k, v);
+    }
+  }
+}
+
+impl IntoIterator for Headers {
+  type Item = (Cow<'static, str>, String);
+  type IntoIter = alloc::vec::IntoIter<(Cow<'static, str>, String)>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.into_vec().into_iter()
+  }
+}
+
+/// Whether the value is a secret: an SSE-C key or its MD5, or a session token.
+fn is_sensitive(name: &str) -> bool {
+  let name = name.to_ascii_lowercase();
+  name.contains("server-side-encryption-customer-key") || name == "x-amz-security-token"
+}
+
+impl fmt::Debug for Headers {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let entries: Vec<(&str, &str)> = self
+      .entries
+      .iter()
+      .map(|(k, v)| match is_sensitive(k) {
+        true => (k.as_ref(), "<redacted>"),
+        false => (k.as_ref(), v.as_str()),
+      })
+      .collect();
+    f.debug_struct("Headers").field("entries", &entries).finish()
+  }
+}
+
+impl Drop for Headers {
+  fn drop(&mut self) {
+    for (name, value) in self.entries.iter_mut() {
+      if is_sensitive(name) {
+        value.zeroize();
+      }
+    }
+  }
+}
+
+impl<K: Into<Cow<'static, str>>, V: Into<String>> From<Vec<(K, V)>> for Headers {
+  fn from(headers: Vec<(K, V)>) -> Self {
+    headers.into_iter().collect()
+  }
+}
+
+impl<K: Into<Cow<'static, str>> + Clone, V: Into<String> + Clone> From<&[(K, V)]> for Headers {
+  fn from(headers: &[(K, V)]) -> Self {
+    headers.iter().cloned().collect()
+  }
+}
+
+impl<K: Into<Cow<'static, str>> + Clone, V: Into<String> + Clone, const N: usize> From<&[(K, V); N]> for Headers {
+  fn from(headers: &[(K, V); N]) -> Self {
+    headers.iter().cloned().collect()
+  }
+}
+
+impl<K: Into<Cow<'static, str>> + Clone, V: Into<String> + Clone> From<&Vec<(K, V)>> for Headers {
+  fn from(headers: &Vec<(K, V)>) -> Self {
+    headers.iter().cloned().collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common_testing::assert;
+
+  #[test]
+  fn insert_replaces_all_with_same_name() {
+    let mut headers: Headers = vec![("a", "1"), ("B", "2"), ("b", "3"), ("c", "4")].into();
+    let old = headers.insert("b", "5");
+
+    assert::equal(old, Some("2".to_owned()));
+    assert::equal(
+      headers.iter().collect::<Vec<_>>(),
+      vec![("a", "1"), ("b", "5"), ("c", "4")],
+    );
+  }
+
+  #[test]
+  fn append_keeps_repeated() {
+    let mut headers = Headers::new();
+    headers.append("x-amz-meta-a", "1");
+    headers.append("X-Amz-Meta-A", "2");
+
+    assert::equal(headers.get_all("x-amz-meta-a").collect::<Vec<_>>(), vec!["1", "2"]);
+    assert::equal(headers.remove("x-amz-meta-a"), Some("1".to_owned()));
+    assert!(headers.is_empty());
+  }
+
+  #[test]
+  fn into_vec_keeps_runtime_names() {
+    let mut headers = Headers::from(&[("Range", "bytes=0-9".to_owned())]);
+    headers.insert(format!("x-amz-meta-{}", "a"), "1");
+    assert::equal(
+      headers.into_vec(),
+      vec![
+        ("Range".into(), "bytes=0-9".to_owned()),
+        ("x-amz-meta-a".into(), "1".to_owned()),
+      ],
+    );
+  }
+
+  #[test]
+  fn into_static_vec_leaves_out_runtime_names() {
+    let mut headers = Headers::from(&[("Range", "bytes=0-9".to_owned())]);
+    headers.insert(format!("x-amz-meta-{}", "a"), "1");
+    assert::equal(headers.into_static_vec(), vec![("Range", "bytes=0-9".to_owned())]);
+  }
+
+  #[test]
+  fn debug_redacts_secrets() {
+    let mut headers = Headers::new();
+    headers.insert("x-amz-server-side-encryption-customer-algorithm", "AES256");
+    headers.insert("x-amz-server-side-encryption-customer-key", "a2V5");
+    headers.insert("X-Amz-Server-Side-Encryption-Customer-Key-MD5", "bWQ1");
+    headers.insert("x-amz-security-token", "token");
+
+    assert::equal(
+      format!("{:?}", headers),
+      "Headers { entries: [\
+      (\"x-amz-server-side-encryption-customer-algorithm\", \"AES256\"), \
+      (\"x-amz-server-side-encryption-customer-key\", \"<redacted>\"), \
+      (\"X-Amz-Server-Side-Encryption-Customer-Key-MD5\", \"<redacted>\"), \
+      (\"x-amz-security-token\", \"<redacted>\")] }"
+        .to_owned(),
+    );
+    assert::equal(headers.get("x-amz-server-side-encryption-customer-key"), Some("a2V5"));
+  }
+}
@@ -0,0 +1,283 @@
+//! Signing `http::Request` values in place.
+//!
+//! Enabled with the `http` feature. Anything that converts to or from the
+//! `http` crate's types (hyper, reqwest, axum, ureq) can use this.
+//!
+use crate::{
+  aws_canonical::to_signable_headers, aws_format, aws_math, get_authorization_header_for, AuthMode, S3HeadersBuilder,
+  EMPTY_PAYLOAD_SHA, UNSIGNED_PAYLOAD,
+};
+use http::{header::HeaderName, HeaderValue, Request};
+use std::{borrow::Cow, fmt};
+use url::Url;
+
+/// How to fill in `x-amz-content-sha256` for the request body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadHash<'a> {
+  /// The body is empty.
+  Empty,
+  /// The body is not signed. Useful for streaming bodies over HTTPS.
+  Unsigned,
+  /// A SHA256 hex digest that was already calculated.
+  Precomputed(&'a str),
+  /// The body content, which will be hashed.
+  Content(&'a [u8]),
+}
+
+impl<'a> PayloadHash<'a> {
+  fn to_hash(self) -> Cow<'a, str> {
+    match self {
+      PayloadHash::Empty => Cow::Borrowed(EMPTY_PAYLOAD_SHA),
+      PayloadHash::Unsigned => Cow::Borrowed(UNSIGNED_PAYLOAD),
+      PayloadHash::Precomputed(hash) => Cow::Borrowed(hash),
+      PayloadHash::Content(content) => Cow::Owned(aws_math::get_sha256(content)),
+    }
+  }
+}
+
+/// Errors that stop a `http::Request` from being signed.
+#[derive(Debug)]
+pub enum SignRequestError {
+  /// The request URI must be absolute, with a scheme and a host.
+  InvalidUri(url::ParseError),
+  /// Header values must be UTF-8 to be signed.
+  InvalidHeaderValue(HeaderName),
+  /// An extra header from the options isn't a valid header name.
+  InvalidHeaderName(String),
+}
+
+impl fmt::Display for SignRequestError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SignRequestError::InvalidUri(err) => write!(f, "request URI must be absolute: {}", err),
+      SignRequestError::InvalidHeaderValue(name) => write!(f, "header {} is not UTF-8", name),
+      SignRequestError::InvalidHeaderName(name) => write!(f, "invalid header name: {:?}", name),
+    }
+  }
+}
+
+impl std::error::Error for SignRequestError {}
+
+/// Signs a `http::Request` in place. Reads the method, URI and headers from
+/// the request, then inserts `host` (if missing), `x-amz-date`,
+/// `x-amz-content-sha256` and `Authorization`.
+///
+/// Only the credentials, region, service, datetime, auth mode, requester pays
+/// and any extra headers are used from the options. Anonymous requests get no
+/// `Authorization`, as with `get_signed_headers`.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{http_request::{sign_request, PayloadHash}, S3DateTime, S3HeadersBuilder};
+/// use url::Url;
+///
+/// let mut request = http::Request::get("https://jsonlog.s3.amazonaws.com/test.json").body(()).unwrap();
+/// let url = Url::parse("https://jsonlog.s3.amazonaws.com/").unwrap();
+/// let options = S3HeadersBuilder::new(&url)
+///   .set_access_key("some_access_key")
+///   .set_secret_key("some_secret_key")
+///   .set_region("some_place")
+///   .set_service("s3")
+///   .set_datetime(S3DateTime::UnixTimestamp(0));
+///
+/// sign_request(&mut request, options, PayloadHash::Empty).unwrap();
+///
+/// assert_eq!(request.headers()["x-amz-date"], "19700101T000000Z");
+/// assert!(request.headers().contains_key("authorization"));
+/// ```
+///
+pub fn sign_request<B>(
+  request: &mut Request<B>,
+  mut options: S3HeadersBuilder,
+  payload_hash: PayloadHash,
+) -> Result<(), SignRequestError> {
+  let url = Url::parse(&request.uri().to_string()).map_err(SignRequestError::InvalidUri)?;
+  let method = request.method().as_str().to_owned();
+  let payload_hash = payload_hash.to_hash();
+  // Read the clock once, so `x-amz-date` is the time that is signed.
+  options.fix_datetime();
+  let amz_date = aws_format::to_long_datetime(&options.datetime.get_offset_datetime());
+  let request_headers = request.headers_mut();
+
+  for (name, value) in options.headers.iter() {
+    let header_name =
+      HeaderName::from_bytes(name.as_bytes()).map_err(|_| SignRequestError::InvalidHeaderName(name.to_owned()))?;
+    request_headers.insert(header_name, to_header_value(name, value)?);
+  }
+  if options.sends_requester_pays() {
+    request_headers.insert("x-amz-request-payer", HeaderValue::from_static("requester"));
+  }
+
+  if !request_headers.contains_key(http::header::HOST) {
+    let host = aws_format::host_header_string(&url);
+    request_headers.insert(http::header::HOST, to_header_value("host", &host)?);
+  }
+  request_headers.insert(
+    "x-amz-content-sha256",
+    to_header_value("x-amz-content-sha256", &payload_hash)?,
+  );
+  request_headers.insert("x-amz-date", to_header_value("x-amz-date", &amz_date)?);
+  if options.auth_mode == AuthMode::Anonymous {
+    request_headers.remove("x-amz-security-token");
+    return Ok(());
+  }
+
+  let headers = to_signable_headers(request_headers.iter().map(|(name, value)| (name, value.as_bytes())))
+    .map_err(|name| SignRequestError::InvalidHeaderValue(name.clone()))?;
+
+  let authorization = get_authorization_header_for(
+    &options
+      .set_url(&url)
+      .set_method(&method)
+      .set_payload_hash(&payload_hash),
+    &headers,
+  );
+
+  request_headers.insert(
+    http::header::AUTHORIZATION,
+    to_header_value("authorization", &authorization)?,
+  );
+  Ok(())
+}
+
+/// Signs a `http::Request` in place, hashing the in-memory body.
+pub fn sign_request_with_body<B: AsRef<[u8]>>(
+  request: &mut Request<B>,
+  options: S3HeadersBuilder,
+) -> Result<(), SignRequestError> {
+  let payload_hash = aws_math::get_sha256(request.body().as_ref());
+  sign_request(request, options, PayloadHash::Precomputed(&payload_hash))
+}
+
+fn to_header_value(name: &str, value: &str) -> Result<HeaderValue, SignRequestError> {
+  HeaderValue::from_str(value).map_err(|_| {
+    SignRequestError::InvalidHeaderValue(HeaderName::from_bytes(name.as_bytes()).expect("Always a valid header name"))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{get_headers, S3DateTime};
+  use common_testing::assert;
+
+  fn get_options(url: &Url) -> S3HeadersBuilder<'_> {
+    S3HeadersBuilder::new(url)
+      .set_access_key("some_access_key")
+      .set_secret_key("some_secret_key")
+      .set_region("some_place")
+      .set_service("s3")
+      .set_datetime(S3DateTime::UnixTimestamp(0))
+  }
+
+  #[test]
+  fn sign_request_matches_get_headers() {
+    let url = Url::parse("https://jsonlog.s3.amazonaws.com/test.json").unwrap();
+    let expected = get_headers(get_options(&url).set_method("GET"));
+
+    let mut request = Request::get(url.as_str()).body(()).unwrap();
+    sign_request(&mut request, get_options(&url), PayloadHash::Empty).unwrap();
+
+    assert::equal(request.headers()["host"].to_str().unwrap(), "jsonlog.s3.amazonaws.com");
+    assert::equal(
+      request.headers()["authorization"].to_str().unwrap(),
+      expected[3].1.as_str(),
+    );
+  }
+
+  #[test]
+  fn sign_request_signs_the_date_it_sends() {
+    let url = Url::parse("https://jsonlog.s3.amazonaws.com/test.json").unwrap();
+    let mut request = Request::get(url.as_str()).body(()).unwrap();
+    sign_request(
+      &mut request,
+      get_options(&url).set_datetime(S3DateTime::Now),
+      PayloadHash::Empty,
+    )
+    .unwrap();
+
+    let amz_date = request.headers()["x-amz-date"].to_str().unwrap();
+    let datetime = aws_format::parse_long_datetime(amz_date).unwrap();
+    let expected = get_headers(
+      get_options(&url)
+        .set_method("GET")
+        .set_datetime(S3DateTime::UnixTimestamp(datetime.unix_timestamp())),
+    );
+    assert::equal(
+      request.headers()["authorization"].to_str().unwrap(),
+      expected[3].1.as_str(),
+    );
+  }
+
+  #[test]
+  fn sign_request_with_body_hashes_content() {
+    let url = Url::parse("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+    let headers = [("x-amz-storage-class", "REDUCED_REDUNDANCY".to_owned())];
+    let expected = get_headers(
+      get_options(&url)
+        .set_method("PUT")
+        .set_headers(&headers)
+        .set_payload_hash_with_content(b"abc"),
+    );
+
+    let mut request = Request::put(url.as_str()).body(b"abc".to_vec()).unwrap();
+    sign_request_with_body(&mut request, get_options(&url).set_headers(&headers)).unwrap();
+
+    assert::equal(
+      request.headers()["x-amz-content-sha256"].to_str().unwrap(),
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+    );
+    assert::equal(
+      request.headers()["x-amz-storage-class"].to_str().unwrap(),
+      "REDUCED_REDUNDANCY",
+    );
+    assert::equal(
+      request.headers()["authorization"].to_str().unwrap(),
+      expected[4].1.as_str(),
+    );
+  }
+
+  #[test]
+  fn sign_request_unsigned_payload_keeps_port() {
+    let mut request = Request::get("http://localhost:9000/bucket/key").body(()).unwrap();
+    let url = Url::parse("http://localhost:9000/").unwrap();
+    sign_request(&mut request, get_options(&url), PayloadHash::Unsigned).unwrap();
+
+    assert::equal(request.headers()["host"].to_str().unwrap(), "localhost:9000");
+    assert::equal(
+      request.headers()["x-amz-content-sha256"].to_str().unwrap(),
+      "UNSIGNED-PAYLOAD",
+    );
+  }
+
+  #[test]
+  fn sign_request_follows_auth_mode_and_requester_pays() {
+    let url = Url::parse("https://jsonlog.s3.amazonaws.com/test.json").unwrap();
+    let options = get_options(&url).set_requester_pays(true);
+    let expected = get_headers(options.clone().set_method("GET"));
+
+    let mut request = Request::get(url.as_str()).body(()).unwrap();
+    sign_request(&mut request, options.clone(), PayloadHash::Empty).unwrap();
+    assert::equal(request.headers()["x-amz-request-payer"].to_str().unwrap(), "requester");
+    assert::equal(
+      request.headers()["authorization"].to_str().unwrap(),
+      expected[4].1.as_str(),
+    );
+
+    let mut request = Request::get(url.as_str()).body(()).unwrap();
+    let anonymous = options.set_auth_mode(AuthMode::Anonymous);
+    sign_request(&mut request, anonymous, PayloadHash::Empty).unwrap();
+    assert!(!request.headers().contains_key("authorization"));
+    assert!(!request.headers().contains_key("x-amz-request-payer"));
+    assert::equal(request.headers()["host"].to_str().unwrap(), "jsonlog.s3.amazonaws.com");
+  }
+
+  #[test]
+  fn sign_request_rejects_relative_uri() {
+    let mut request = Request::get("/bucket/key").body(()).unwrap();
+    let url = Url::parse("http://localhost/").unwrap();
+    let result = sign_request(&mut request, get_options(&url), PayloadHash::Empty);
+    assert::err(&result);
+  }
+}
@@ -1,9 +1,69 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+mod acl;
 pub mod aws_canonical;
 pub mod aws_format;
 pub mod aws_math;
+pub mod aws_stream;
+mod clock;
+mod conditions;
+mod credentials;
+mod event_stream;
+mod headers;
+#[cfg(feature = "http")]
+pub mod http_request;
+mod list;
+mod metadata;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
+mod object;
+mod post_policy;
+mod provider;
+mod range;
+mod region;
+#[cfg(feature = "reqwest")]
+pub mod reqwest_ext;
+mod retry;
 mod s3;
+mod s3_v2;
+mod select;
+mod signature_mismatch;
+mod sse;
+mod validation;
 
+pub use acl::*;
+pub use clock::*;
+pub use conditions::*;
+pub use credentials::*;
+pub use event_stream::*;
+pub use headers::*;
+pub use list::*;
+pub use metadata::*;
+pub use object::*;
+pub use post_policy::*;
+pub use provider::*;
+pub use range::*;
+pub use region::*;
+pub use retry::*;
 pub use s3::*;
+pub use s3_v2::*;
+pub use select::*;
+pub use signature_mismatch::*;
+pub use sse::*;
+pub use validation::*;
+
+/// What the `std` prelude would give us, for `no_std` builds.
+mod prelude {
+  pub use alloc::{
+    borrow::ToOwned,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+  };
+}
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,280 @@
+//! Object-level headers: storage class, object lock and legal hold.
+//!
+use crate::aws_format;
+use crate::prelude::*;
+use core::{fmt, str::FromStr};
+use time::OffsetDateTime;
+
+/// The storage class of an object.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::StorageClass;
+///
+/// let header = StorageClass::ReducedRedundancy.to_header();
+/// assert_eq!(header, ("x-amz-storage-class", "REDUCED_REDUNDANCY".to_owned()));
+///
+/// let storage_class: StorageClass = "GLACIER_IR".parse().unwrap();
+/// assert_eq!(storage_class, StorageClass::GlacierInstantRetrieval);
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html#AmazonS3-PutObject-request-header-StorageClass
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StorageClass {
+  #[default]
+  Standard,
+  ReducedRedundancy,
+  StandardInfrequentAccess,
+  OneZoneInfrequentAccess,
+  IntelligentTiering,
+  Glacier,
+  DeepArchive,
+  Outposts,
+  GlacierInstantRetrieval,
+  Snow,
+  ExpressOneZone,
+}
+
+impl StorageClass {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      StorageClass::Standard => "STANDARD",
+      StorageClass::ReducedRedundancy => "REDUCED_REDUNDANCY",
+      StorageClass::StandardInfrequentAccess => "STANDARD_IA",
+      StorageClass::OneZoneInfrequentAccess => "ONEZONE_IA",
+      StorageClass::IntelligentTiering => "INTELLIGENT_TIERING",
+      StorageClass::Glacier => "GLACIER",
+      StorageClass::DeepArchive => "DEEP_ARCHIVE",
+      StorageClass::Outposts => "OUTPOSTS",
+      StorageClass::GlacierInstantRetrieval => "GLACIER_IR",
+      StorageClass::Snow => "SNOW",
+      StorageClass::ExpressOneZone => "EXPRESS_ONEZONE",
+    }
+  }
+
+  pub fn to_header(&self) -> (&'static str, String) {
+    ("x-amz-storage-class", self.as_str().to_owned())
+  }
+}
+
+impl fmt::Display for StorageClass {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for StorageClass {
+  type Err = UnknownValueError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "STANDARD" => Ok(StorageClass::Standard),
+      "REDUCED_REDUNDANCY" => Ok(StorageClass::ReducedRedundancy),
+      "STANDARD_IA" => Ok(StorageClass::StandardInfrequentAccess),
+      "ONEZONE_IA" => Ok(StorageClass::OneZoneInfrequentAccess),
+      "INTELLIGENT_TIERING" => Ok(StorageClass::IntelligentTiering),
+      "GLACIER" => Ok(StorageClass::Glacier),
+      "DEEP_ARCHIVE" => Ok(StorageClass::DeepArchive),
+      "OUTPOSTS" => Ok(StorageClass::Outposts),
+      "GLACIER_IR" => Ok(StorageClass::GlacierInstantRetrieval),
+      "SNOW" => Ok(StorageClass::Snow),
+      "EXPRESS_ONEZONE" => Ok(StorageClass::ExpressOneZone),
+      _ => Err(UnknownValueError(s.to_owned())),
+    }
+  }
+}
+
+/// The object lock retention mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectLockMode {
+  Governance,
+  Compliance,
+}
+
+impl ObjectLockMode {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ObjectLockMode::Governance => "GOVERNANCE",
+      ObjectLockMode::Compliance => "COMPLIANCE",
+    }
+  }
+}
+
+impl fmt::Display for ObjectLockMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for ObjectLockMode {
+  type Err = UnknownValueError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "GOVERNANCE" => Ok(ObjectLockMode::Governance),
+      "COMPLIANCE" => Ok(ObjectLockMode::Compliance),
+      _ => Err(UnknownValueError(s.to_owned())),
+    }
+  }
+}
+
+/// Object lock retention, applied when the object is written.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{ObjectLockMode, ObjectLockRetention};
+/// use time::OffsetDateTime;
+///
+/// let retain_until = OffsetDateTime::from_unix_timestamp(1_000_000_000).unwrap();
+/// let headers = ObjectLockRetention::new(ObjectLockMode::Governance, retain_until).to_headers();
+/// assert_eq!(headers, vec![
+///   ("x-amz-object-lock-mode", "GOVERNANCE".to_owned()),
+///   ("x-amz-object-lock-retain-until-date", "2001-09-09T01:46:40Z".to_owned()),
+/// ]);
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/userguide/object-lock.html
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectLockRetention {
+  pub mode: ObjectLockMode,
+  pub retain_until: OffsetDateTime,
+}
+
+impl ObjectLockRetention {
+  pub fn new(mode: ObjectLockMode, retain_until: OffsetDateTime) -> Self {
+    Self { mode, retain_until }
+  }
+
+  pub fn to_headers(&self) -> Vec<(&'static str, String)> {
+    vec![
+      ("x-amz-object-lock-mode", self.mode.as_str().to_owned()),
+      (
+        "x-amz-object-lock-retain-until-date",
+        aws_format::to_iso8601_datetime(&self.retain_until),
+      ),
+    ]
+  }
+}
+
+/// The object lock legal hold status.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::LegalHold;
+///
+/// assert_eq!(LegalHold::On.to_header(), ("x-amz-object-lock-legal-hold", "ON".to_owned()));
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegalHold {
+  On,
+  Off,
+}
+
+impl LegalHold {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LegalHold::On => "ON",
+      LegalHold::Off => "OFF",
+    }
+  }
+
+  pub fn to_header(&self) -> (&'static str, String) {
+    ("x-amz-object-lock-legal-hold", self.as_str().to_owned())
+  }
+}
+
+impl fmt::Display for LegalHold {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for LegalHold {
+  type Err = UnknownValueError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "ON" => Ok(LegalHold::On),
+      "OFF" => Ok(LegalHold::Off),
+      _ => Err(UnknownValueError(s.to_owned())),
+    }
+  }
+}
+
+/// A header value that doesn't match any of the known values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownValueError(pub String);
+
+impl fmt::Display for UnknownValueError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "unknown value: {:?}", self.0)
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownValueError {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common_testing::assert;
+
+  #[test]
+  fn storage_class_round_trips() {
+    let all = [
+      StorageClass::Standard,
+      StorageClass::ReducedRedundancy,
+      StorageClass::StandardInfrequentAccess,
+      StorageClass::OneZoneInfrequentAccess,
+      StorageClass::IntelligentTiering,
+      StorageClass::Glacier,
+      StorageClass::DeepArchive,
+      StorageClass::Outposts,
+      StorageClass::GlacierInstantRetrieval,
+      StorageClass::Snow,
+      StorageClass::ExpressOneZone,
+    ];
+
+    for storage_class in all {
+      assert::equal(StorageClass::from_str(storage_class.as_str()), storage_class);
+    }
+  }
+
+  #[test]
+  fn storage_class_rejects_typos() {
+    let result = StorageClass::from_str("REDUCED_REDUNDENCY");
+    assert::equal(result.unwrap_err().to_string(), "unknown value: \"REDUCED_REDUNDENCY\"");
+  }
+
+  #[test]
+  fn object_lock_retention_headers() {
+    let retain_until = OffsetDateTime::from_unix_timestamp(0).unwrap();
+    let result = ObjectLockRetention::new(ObjectLockMode::Compliance, retain_until).to_headers();
+    assert::equal(
+      result,
+      vec![
+        ("x-amz-object-lock-mode", "COMPLIANCE".to_owned()),
+        ("x-amz-object-lock-retain-until-date", "1970-01-01T00:00:00Z".to_owned()),
+      ],
+    );
+  }
+
+  #[test]
+  fn legal_hold_header() {
+    assert::equal(
+      LegalHold::Off.to_header(),
+      ("x-amz-object-lock-legal-hold", "OFF".to_owned()),
+    );
+    assert::equal(LegalHold::from_str("ON"), LegalHold::On);
+  }
+}
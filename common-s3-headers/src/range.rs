@@ -0,0 +1,343 @@
+//! Byte ranges for ranged GETs, resumable downloads and parallel downloads.
+//!
+use crate::prelude::*;
+use core::{fmt, str::FromStr};
+
+/// Errors from building or parsing a byte range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidRangeError {
+  /// The last byte is before the first byte.
+  EndBeforeStart { start: u64, end: u64 },
+  /// A suffix range must ask for at least one byte.
+  EmptySuffix,
+  /// The header value isn't a byte range.
+  Malformed(String),
+}
+
+impl fmt::Display for InvalidRangeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      InvalidRangeError::EndBeforeStart { start, end } => {
+        write!(f, "range end {} is before range start {}", end, start)
+      }
+      InvalidRangeError::EmptySuffix => write!(f, "suffix range must be at least one byte"),
+      InvalidRangeError::Malformed(value) => write!(f, "malformed byte range: {:?}", value),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidRangeError {}
+
+/// A single byte range for the `Range` header. Both ends are inclusive. S3
+/// only serves one range per request. `new` and `suffix` check the range;
+/// `to_header` checks ranges built from the variants directly.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::ByteRange;
+///
+/// assert_eq!(ByteRange::new(0, 499).unwrap().to_string(), "bytes=0-499");
+/// assert_eq!(ByteRange::From(500).to_string(), "bytes=500-");
+/// assert_eq!(ByteRange::suffix(500).unwrap().to_string(), "bytes=-500");
+///
+/// assert!(ByteRange::new(500, 499).is_err());
+/// assert!(ByteRange::suffix(0).is_err());
+/// assert!(ByteRange::FromTo(500, 499).to_header().is_err());
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html#API_GetObject_RequestSyntax
+/// * https://www.rfc-editor.org/rfc/rfc9110#name-byte-ranges
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+  /// From the first byte to the last byte.
+  FromTo(u64, u64),
+  /// From the first byte to the end of the object.
+  From(u64),
+  /// The last n bytes of the object.
+  Suffix(u64),
+}
+
+impl ByteRange {
+  pub fn new(start: u64, end: u64) -> Result<Self, InvalidRangeError> {
+    let range = ByteRange::FromTo(start, end);
+    range.validate()?;
+    Ok(range)
+  }
+
+  pub fn suffix(length: u64) -> Result<Self, InvalidRangeError> {
+    let range = ByteRange::Suffix(length);
+    range.validate()?;
+    Ok(range)
+  }
+
+  /// Fails if the end is before the start, or a suffix range is empty.
+  pub fn validate(&self) -> Result<(), InvalidRangeError> {
+    match *self {
+      ByteRange::FromTo(start, end) if end < start => Err(InvalidRangeError::EndBeforeStart { start, end }),
+      ByteRange::Suffix(0) => Err(InvalidRangeError::EmptySuffix),
+      _ => Ok(()),
+    }
+  }
+
+  /// Gets the number of bytes in the range for an object of this size, or
+  /// `None` if the range can't be satisfied.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use common_s3_headers::ByteRange;
+  ///
+  /// assert_eq!(ByteRange::FromTo(0, 499).len_for(1000), Some(500));
+  /// assert_eq!(ByteRange::FromTo(900, 1999).len_for(1000), Some(100));
+  /// assert_eq!(ByteRange::From(1000).len_for(1000), None);
+  /// assert_eq!(ByteRange::Suffix(5000).len_for(1000), Some(1000));
+  /// ```
+  ///
+  pub fn len_for(&self, size: u64) -> Option<u64> {
+    match *self {
+      ByteRange::FromTo(start, end) if start < size && start <= end => Some(end.min(size - 1) - start + 1),
+      ByteRange::From(start) if start < size => Some(size - start),
+      ByteRange::Suffix(length) if size > 0 && length > 0 => Some(length.min(size)),
+      _ => None,
+    }
+  }
+
+  pub fn to_header(&self) -> Result<(&'static str, String), InvalidRangeError> {
+    self.validate()?;
+    Ok(("Range", self.to_string()))
+  }
+}
+
+impl fmt::Display for ByteRange {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ByteRange::FromTo(start, end) => write!(f, "bytes={}-{}", start, end),
+      ByteRange::From(start) => write!(f, "bytes={}-", start),
+      ByteRange::Suffix(length) => write!(f, "bytes=-{}", length),
+    }
+  }
+}
+
+impl FromStr for ByteRange {
+  type Err = InvalidRangeError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let malformed = || InvalidRangeError::Malformed(s.to_owned());
+    let spec = s.trim().strip_prefix("bytes=").ok_or_else(malformed)?;
+    let (start, end) = spec.split_once('-').ok_or_else(malformed)?;
+    let parse = |value: &str| value.parse::<u64>().map_err(|_| malformed());
+
+    match (start.is_empty(), end.is_empty()) {
+      (false, false) => ByteRange::new(parse(start)?, parse(end)?),
+      (false, true) => Ok(ByteRange::From(parse(start)?)),
+      (true, false) => ByteRange::suffix(parse(end)?),
+      (true, true) => Err(malformed()),
+    }
+  }
+}
+
+/// The `Content-Range` header of a partial response: the inclusive byte range
+/// that was sent, and the full size of the object if the server knows it.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::ContentRange;
+///
+/// let result: ContentRange = "bytes 0-499/1234".parse().unwrap();
+/// assert_eq!(result, ContentRange { start: 0, end: 499, total: Some(1234) });
+/// assert_eq!(result.len(), Some(500));
+///
+/// // Resume from the next byte.
+/// assert_eq!(result.next_range().unwrap().to_string(), "bytes=500-1233");
+/// ```
+///
+/// # See
+///
+/// * https://www.rfc-editor.org/rfc/rfc9110#name-content-range
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+  pub start: u64,
+  pub end: u64,
+  pub total: Option<u64>,
+}
+
+impl ContentRange {
+  /// Gets the number of bytes sent, or `None` if the end is before the
+  /// start or the range covers all of `u64`.
+  #[allow(clippy::len_without_is_empty)]
+  pub fn len(&self) -> Option<u64> {
+    self.end.checked_sub(self.start)?.checked_add(1)
+  }
+
+  /// Gets the range for the rest of the object, or `None` if this was the
+  /// last part, the total size is unknown or the range is invalid.
+  pub fn next_range(&self) -> Option<ByteRange> {
+    let total = self.total?;
+    let next = self.end.checked_add(1)?;
+    if self.end < self.start || next >= total {
+      return None;
+    }
+    Some(ByteRange::FromTo(next, total - 1))
+  }
+}
+
+impl FromStr for ContentRange {
+  type Err = InvalidRangeError;
+
+  /// Parses `bytes start-end/total` or `bytes start-end/*`. The unsatisfied
+  /// form, `bytes */total`, has no range and is an error.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let malformed = || InvalidRangeError::Malformed(s.to_owned());
+    let spec = s.trim().strip_prefix("bytes ").ok_or_else(malformed)?;
+    let (range, total) = spec.split_once('/').ok_or_else(malformed)?;
+    let (start, end) = range.split_once('-').ok_or_else(malformed)?;
+    let parse = |value: &str| value.trim().parse::<u64>().map_err(|_| malformed());
+
+    let (start, end) = (parse(start)?, parse(end)?);
+    if end < start {
+      return Err(InvalidRangeError::EndBeforeStart { start, end });
+    }
+    let total = match total.trim() {
+      "*" => None,
+      total => Some(parse(total)?),
+    };
+    if total.is_some_and(|total| end >= total) {
+      return Err(malformed());
+    }
+    Ok(ContentRange { start, end, total })
+  }
+}
+
+/// Splits an object into at most `parts` ranges of nearly equal size, for
+/// parallel ranged GETs. Earlier ranges get the extra bytes. An empty object
+/// has no ranges.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{plan_ranges, ByteRange};
+///
+/// assert_eq!(
+///   plan_ranges(10, 3),
+///   vec![ByteRange::FromTo(0, 3), ByteRange::FromTo(4, 6), ByteRange::FromTo(7, 9)]
+/// );
+/// assert_eq!(plan_ranges(2, 4), vec![ByteRange::FromTo(0, 0), ByteRange::FromTo(1, 1)]);
+/// assert!(plan_ranges(0, 4).is_empty());
+/// ```
+///
+pub fn plan_ranges(size: u64, parts: u64) -> Vec<ByteRange> {
+  let parts = parts.clamp(1, size.max(1));
+  if size == 0 {
+    return Vec::new();
+  }
+  let base = size / parts;
+  let extra = size % parts;
+
+  let mut ranges = Vec::with_capacity(parts as usize);
+  let mut start = 0;
+  for i in 0..parts {
+    let len = base + u64::from(i < extra);
+    ranges.push(ByteRange::FromTo(start, start + len - 1));
+    start += len;
+  }
+  ranges
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common_testing::assert;
+
+  #[test]
+  fn byte_range_round_trips() {
+    for value in ["bytes=0-499", "bytes=500-", "bytes=-500"] {
+      assert::equal(value.parse::<ByteRange>().unwrap().to_string(), value.to_owned());
+    }
+  }
+
+  #[test]
+  fn byte_range_rejects_invalid() {
+    assert::equal(
+      "bytes=5-4".parse::<ByteRange>(),
+      Err(InvalidRangeError::EndBeforeStart { start: 5, end: 4 }),
+    );
+    assert::equal("bytes=-0".parse::<ByteRange>(), Err(InvalidRangeError::EmptySuffix));
+    assert::err(&"bytes=-".parse::<ByteRange>());
+    assert::err(&"bytes=0-1,5-6".parse::<ByteRange>());
+    assert::err(&"items=0-1".parse::<ByteRange>());
+  }
+
+  #[test]
+  fn byte_range_variants_are_checked() {
+    assert::equal(
+      ByteRange::FromTo(10, 5).to_header(),
+      Err(InvalidRangeError::EndBeforeStart { start: 10, end: 5 }),
+    );
+    assert::equal(ByteRange::Suffix(0).to_header(), Err(InvalidRangeError::EmptySuffix));
+    assert::equal(ByteRange::FromTo(10, 5).len_for(100), None);
+    assert::equal(ByteRange::From(0).to_header(), Ok(("Range", "bytes=0-".to_owned())));
+  }
+
+  #[test]
+  fn content_range_unknown_total() {
+    let result: ContentRange = "bytes 100-199/*".parse().unwrap();
+    assert::equal(result.total, None);
+    assert::none(&result.next_range());
+  }
+
+  #[test]
+  fn content_range_last_part_has_no_next_range() {
+    let result: ContentRange = "bytes 500-1233/1234".parse().unwrap();
+    assert::none(&result.next_range());
+  }
+
+  #[test]
+  fn content_range_built_by_hand_does_not_overflow() {
+    let reversed = ContentRange {
+      start: 5,
+      end: 1,
+      total: Some(10),
+    };
+    assert::none(&reversed.len());
+    assert::none(&reversed.next_range());
+
+    let everything = ContentRange {
+      start: 0,
+      end: u64::MAX,
+      total: Some(u64::MAX),
+    };
+    assert::none(&everything.len());
+    assert::none(&everything.next_range());
+  }
+
+  #[test]
+  fn content_range_rejects_invalid() {
+    assert::err(&"bytes */1234".parse::<ContentRange>());
+    assert::err(&"bytes 0-1234/1234".parse::<ContentRange>());
+    assert::err(&"bytes 10-0/1234".parse::<ContentRange>());
+    assert::err(&"0-10/1234".parse::<ContentRange>());
+  }
+
+  #[test]
+  fn plan_ranges_covers_object() {
+    let size = 1_000_003;
+    let ranges = plan_ranges(size, 8);
+    assert::equal(ranges.len(), 8);
+    assert::equal(ranges[0], ByteRange::FromTo(0, 125_000));
+    assert::equal(ranges[7], ByteRange::FromTo(875_003, 1_000_002));
+    let total: u64 = ranges.iter().map(|range| range.len_for(size).unwrap()).sum();
+    assert::equal(total, size);
+  }
+
+  #[test]
+  fn plan_ranges_zero_parts_is_one_part() {
+    assert::equal(plan_ranges(10, 0), vec![ByteRange::FromTo(0, 9)]);
+  }
+}
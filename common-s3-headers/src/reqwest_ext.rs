@@ -0,0 +1,423 @@
+//! Signing reqwest requests.
+//!
+//! Enabled with the `reqwest` feature, and `reqwest-blocking` for the
+//! blocking client. In-memory bodies are hashed. Streaming bodies are sent
+//! with an unsigned payload.
+//!
+use crate::{
+  aws_canonical::to_signable_headers, aws_format, aws_math, get_authorization_header_for, AuthMode, Credentials,
+  S3DateTime, S3HeadersBuilder, EMPTY_PAYLOAD_SHA, UNSIGNED_PAYLOAD,
+};
+use reqwest::{
+  header::{HeaderMap, HeaderName, HeaderValue, InvalidHeaderName, InvalidHeaderValue, AUTHORIZATION, HOST},
+  Method,
+};
+use std::{borrow::Cow, fmt};
+use url::Url;
+
+/// Errors that stop a reqwest request from being signed.
+#[derive(Debug)]
+pub enum SignS3Error {
+  /// The request could not be built.
+  Request(reqwest::Error),
+  /// A generated header, such as one containing the access key, isn't visible ASCII.
+  InvalidHeaderValue(InvalidHeaderValue),
+  /// An extra header from the options isn't a valid header name.
+  InvalidHeaderName(InvalidHeaderName),
+  /// Header values must be UTF-8 to be signed.
+  NonUtf8HeaderValue(HeaderName),
+}
+
+impl fmt::Display for SignS3Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SignS3Error::Request(err) => write!(f, "request could not be built: {}", err),
+      SignS3Error::InvalidHeaderValue(err) => write!(f, "header could not be signed: {}", err),
+      SignS3Error::InvalidHeaderName(err) => write!(f, "header could not be signed: {}", err),
+      SignS3Error::NonUtf8HeaderValue(name) => write!(f, "header {} is not UTF-8", name),
+    }
+  }
+}
+
+impl std::error::Error for SignS3Error {}
+
+impl From<reqwest::Error> for SignS3Error {
+  fn from(err: reqwest::Error) -> Self {
+    SignS3Error::Request(err)
+  }
+}
+
+impl From<InvalidHeaderValue> for SignS3Error {
+  fn from(err: InvalidHeaderValue) -> Self {
+    SignS3Error::InvalidHeaderValue(err)
+  }
+}
+
+impl From<InvalidHeaderName> for SignS3Error {
+  fn from(err: InvalidHeaderName) -> Self {
+    SignS3Error::InvalidHeaderName(err)
+  }
+}
+
+/// Signs a built request in place, for the `s3` service.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{reqwest_ext::SignS3Request, Credentials};
+///
+/// let credentials = Credentials::new("some_access_key", "some_secret_key");
+/// let mut request = reqwest::Client::new()
+///   .put("https://examplebucket.s3.amazonaws.com/test.txt")
+///   .body("abc")
+///   .build()
+///   .unwrap();
+///
+/// request.sign_s3(&credentials, "us-east-1").unwrap();
+///
+/// assert_eq!(
+///   request.headers()["x-amz-content-sha256"],
+///   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+/// );
+/// assert!(request.headers().contains_key("authorization"));
+/// ```
+///
+pub trait SignS3Request {
+  /// Signs with the system clock.
+  fn sign_s3(&mut self, credentials: &Credentials, region: &str) -> Result<(), SignS3Error> {
+    self.sign_s3_at(credentials, region, S3DateTime::Now)
+  }
+
+  /// Signs at the given time, such as `clock.to_s3_datetime()` from a `Clock`
+  /// that corrects for clock skew.
+  fn sign_s3_at(&mut self, credentials: &Credentials, region: &str, datetime: S3DateTime) -> Result<(), SignS3Error>;
+
+  /// Signs with the options' credentials, region, service, datetime, auth
+  /// mode, requester pays and extra headers. The method, URL and payload
+  /// hash come from the request. Anonymous requests get no `Authorization`.
+  fn sign_s3_with(&mut self, options: S3HeadersBuilder) -> Result<(), SignS3Error>;
+}
+
+impl SignS3Request for reqwest::Request {
+  fn sign_s3_at(&mut self, credentials: &Credentials, region: &str, datetime: S3DateTime) -> Result<(), SignS3Error> {
+    let url = self.url().clone();
+    self.sign_s3_with(get_options(&url, credentials, region, datetime))
+  }
+
+  fn sign_s3_with(&mut self, options: S3HeadersBuilder) -> Result<(), SignS3Error> {
+    let payload_hash = get_payload_hash(self.body().map(|body| body.as_bytes()));
+    let method = self.method().clone();
+    let url = self.url().clone();
+    sign_parts(&method, &url, self.headers_mut(), &payload_hash, options)
+  }
+}
+
+#[cfg(feature = "reqwest-blocking")]
+impl SignS3Request for reqwest::blocking::Request {
+  fn sign_s3_at(&mut self, credentials: &Credentials, region: &str, datetime: S3DateTime) -> Result<(), SignS3Error> {
+    let url = self.url().clone();
+    self.sign_s3_with(get_options(&url, credentials, region, datetime))
+  }
+
+  fn sign_s3_with(&mut self, options: S3HeadersBuilder) -> Result<(), SignS3Error> {
+    let payload_hash = get_payload_hash(self.body().map(|body| body.as_bytes()));
+    let method = self.method().clone();
+    let url = self.url().clone();
+    sign_parts(&method, &url, self.headers_mut(), &payload_hash, options)
+  }
+}
+
+/// Signs a request builder, for the `s3` service. Call this last, after the
+/// URL, headers and body are set.
+///
+/// The blocking `RequestBuilder` can't be rebuilt from a request, so build the
+/// blocking request and use `SignS3Request` instead.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{reqwest_ext::SignS3RequestBuilder, Credentials};
+///
+/// let credentials = Credentials::new("some_access_key", "some_secret_key");
+/// let builder = reqwest::Client::new()
+///   .get("https://examplebucket.s3.amazonaws.com/test.txt")
+///   .sign_s3(&credentials, "us-east-1")
+///   .unwrap();
+///
+/// let request = builder.build().unwrap();
+/// assert!(request.headers().contains_key("authorization"));
+/// ```
+///
+pub trait SignS3RequestBuilder: Sized {
+  /// Signs with the system clock.
+  fn sign_s3(self, credentials: &Credentials, region: &str) -> Result<Self, SignS3Error> {
+    self.sign_s3_at(credentials, region, S3DateTime::Now)
+  }
+
+  /// Signs at the given time, such as `clock.to_s3_datetime()` from a `Clock`
+  /// that corrects for clock skew.
+  fn sign_s3_at(self, credentials: &Credentials, region: &str, datetime: S3DateTime) -> Result<Self, SignS3Error>;
+
+  /// Signs with the options, like `SignS3Request::sign_s3_with`.
+  fn sign_s3_with(self, options: S3HeadersBuilder) -> Result<Self, SignS3Error>;
+}
+
+impl SignS3RequestBuilder for reqwest::RequestBuilder {
+  fn sign_s3_at(self, credentials: &Credentials, region: &str, datetime: S3DateTime) -> Result<Self, SignS3Error> {
+    let (client, request) = self.build_split();
+    let mut request = request?;
+    request.sign_s3_at(credentials, region, datetime)?;
+    Ok(reqwest::RequestBuilder::from_parts(client, request))
+  }
+
+  fn sign_s3_with(self, options: S3HeadersBuilder) -> Result<Self, SignS3Error> {
+    let (client, request) = self.build_split();
+    let mut request = request?;
+    request.sign_s3_with(options)?;
+    Ok(reqwest::RequestBuilder::from_parts(client, request))
+  }
+}
+
+/// No body is the empty payload, in-memory bodies are hashed and streams are unsigned.
+fn get_payload_hash(body: Option<Option<&[u8]>>) -> Cow<'static, str> {
+  match body {
+    None => Cow::Borrowed(EMPTY_PAYLOAD_SHA),
+    Some(Some(bytes)) => Cow::Owned(aws_math::get_sha256(bytes)),
+    Some(None) => Cow::Borrowed(UNSIGNED_PAYLOAD),
+  }
+}
+
+/// The options for the `s3` service, with the session token as an extra header.
+fn get_options<'a>(
+  url: &'a Url,
+  credentials: &'a Credentials,
+  region: &'a str,
+  datetime: S3DateTime,
+) -> S3HeadersBuilder<'a> {
+  let options = S3HeadersBuilder::new(url)
+    .set_datetime(datetime)
+    .set_access_key(&credentials.access_key)
+    .set_secret_key(&credentials.secret_key)
+    .set_region(region)
+    .set_service("s3");
+  match credentials.to_session_token_header() {
+    Some((name, value)) => options.set_header(name, value),
+    None => options,
+  }
+}
+
+fn sign_parts(
+  method: &Method,
+  url: &Url,
+  request_headers: &mut HeaderMap,
+  payload_hash: &str,
+  options: S3HeadersBuilder,
+) -> Result<(), SignS3Error> {
+  // Read the clock once, so `x-amz-date` is the time that is signed.
+  let mut options = options
+    .set_url(url)
+    .set_method(method.as_str())
+    .set_payload_hash(payload_hash);
+  options.fix_datetime();
+  let amz_date = aws_format::to_long_datetime(&options.datetime.get_offset_datetime());
+
+  for (name, value) in options.headers.iter() {
+    request_headers.insert(HeaderName::from_bytes(name.as_bytes())?, HeaderValue::from_str(value)?);
+  }
+  if options.sends_requester_pays() {
+    request_headers.insert("x-amz-request-payer", HeaderValue::from_static("requester"));
+  }
+
+  if !request_headers.contains_key(HOST) {
+    request_headers.insert(HOST, HeaderValue::from_str(&aws_format::host_header_string(url))?);
+  }
+  request_headers.insert("x-amz-content-sha256", HeaderValue::from_str(payload_hash)?);
+  request_headers.insert("x-amz-date", HeaderValue::from_str(&amz_date)?);
+  if options.auth_mode == AuthMode::Anonymous {
+    request_headers.remove("x-amz-security-token");
+    return Ok(());
+  }
+
+  let headers = to_signable_headers(request_headers.iter().map(|(name, value)| (name, value.as_bytes())))
+    .map_err(|name| SignS3Error::NonUtf8HeaderValue(name.clone()))?;
+
+  let authorization = get_authorization_header_for(&options, &headers);
+  request_headers.insert(AUTHORIZATION, HeaderValue::from_str(&authorization)?);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{get_headers, Clock, FixedClock};
+  use common_testing::assert;
+
+  fn get_credentials() -> Credentials {
+    Credentials::new("some_access_key", "some_secret_key")
+  }
+
+  #[test]
+  fn sign_parts_matches_get_headers() {
+    let url = Url::parse("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+    let mut request = reqwest::Client::new().get(url.clone()).build().unwrap();
+    let datetime = S3DateTime::UnixTimestamp(0);
+    let expected = get_headers(
+      S3HeadersBuilder::new(&url)
+        .set_access_key("some_access_key")
+        .set_secret_key("some_secret_key")
+        .set_region("some_place")
+        .set_service("s3")
+        .set_method("GET")
+        .set_datetime(datetime),
+    );
+
+    let (method, url) = (request.method().clone(), request.url().clone());
+    let credentials = get_credentials();
+    sign_parts(
+      &method,
+      &url,
+      request.headers_mut(),
+      EMPTY_PAYLOAD_SHA,
+      get_options(&url, &credentials, "some_place", datetime),
+    )
+    .unwrap();
+
+    assert::equal(request.headers()["x-amz-date"].to_str().unwrap(), "19700101T000000Z");
+    assert::equal(
+      request.headers()["authorization"].to_str().unwrap(),
+      expected[3].1.as_str(),
+    );
+  }
+
+  #[test]
+  fn sign_s3_at_uses_clock_time() {
+    let clock = FixedClock::from_unix_timestamp(1369353600);
+    let mut request = reqwest::Client::new()
+      .get("https://examplebucket.s3.amazonaws.com/test.txt")
+      .build()
+      .unwrap();
+    request
+      .sign_s3_at(&get_credentials(), "us-east-1", clock.to_s3_datetime())
+      .unwrap();
+
+    assert::equal(request.headers()["x-amz-date"].to_str().unwrap(), "20130524T000000Z");
+    assert!(request.headers()["authorization"]
+      .to_str()
+      .unwrap()
+      .contains("/20130524/us-east-1/s3/aws4_request,"));
+  }
+
+  #[test]
+  fn sign_s3_signs_the_date_it_sends() {
+    let url = Url::parse("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+    let mut request = reqwest::Client::new().get(url.clone()).build().unwrap();
+    request.sign_s3(&get_credentials(), "some_place").unwrap();
+
+    let amz_date = request.headers()["x-amz-date"].to_str().unwrap();
+    let datetime = aws_format::parse_long_datetime(amz_date).unwrap();
+    let expected = get_headers(
+      S3HeadersBuilder::new(&url)
+        .set_access_key("some_access_key")
+        .set_secret_key("some_secret_key")
+        .set_region("some_place")
+        .set_service("s3")
+        .set_method("GET")
+        .set_datetime(S3DateTime::UnixTimestamp(datetime.unix_timestamp())),
+    );
+    assert::equal(
+      request.headers()["authorization"].to_str().unwrap(),
+      expected[3].1.as_str(),
+    );
+  }
+
+  #[test]
+  fn get_payload_hash_by_body_kind() {
+    assert::equal(get_payload_hash(None), EMPTY_PAYLOAD_SHA);
+    assert::equal(get_payload_hash(Some(None)), UNSIGNED_PAYLOAD);
+    assert::equal(
+      get_payload_hash(Some(Some(b"abc"))),
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+    );
+  }
+
+  #[test]
+  fn sign_s3_signs_session_token() {
+    let credentials = get_credentials().set_session_token("token");
+    let mut request = reqwest::Client::new()
+      .get("https://examplebucket.s3.amazonaws.com/test.txt")
+      .build()
+      .unwrap();
+    request.sign_s3(&credentials, "us-east-1").unwrap();
+
+    assert::equal(request.headers()["x-amz-security-token"].to_str().unwrap(), "token");
+    assert!(request.headers()["authorization"]
+      .to_str()
+      .unwrap()
+      .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-security-token,"));
+  }
+
+  #[test]
+  fn sign_s3_trims_values_and_rejects_non_utf8() {
+    let sign = |value: &[u8]| {
+      let mut request = reqwest::Client::new()
+        .get("https://examplebucket.s3.amazonaws.com/test.txt")
+        .header("x-amz-meta-a", HeaderValue::from_bytes(value).unwrap())
+        .build()
+        .unwrap();
+      request
+        .sign_s3_at(&get_credentials(), "us-east-1", S3DateTime::UnixTimestamp(0))
+        .map(|_| request.headers()["authorization"].clone())
+    };
+
+    assert::equal(sign(b" a ").unwrap(), sign(b"a").unwrap());
+    assert!(matches!(sign(b"\xff"), Err(SignS3Error::NonUtf8HeaderValue(name)) if name == "x-amz-meta-a"));
+  }
+
+  #[test]
+  fn sign_s3_with_follows_auth_mode_and_requester_pays() {
+    let url = Url::parse("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+    let options = S3HeadersBuilder::new(&url)
+      .set_access_key("some_access_key")
+      .set_secret_key("some_secret_key")
+      .set_region("some_place")
+      .set_service("s3")
+      .set_datetime(S3DateTime::UnixTimestamp(0))
+      .set_requester_pays(true);
+    let expected = get_headers(options.clone().set_method("GET"));
+
+    let mut request = reqwest::Client::new().get(url.clone()).build().unwrap();
+    request.sign_s3_with(options.clone()).unwrap();
+    assert::equal(request.headers()["x-amz-request-payer"].to_str().unwrap(), "requester");
+    assert::equal(
+      request.headers()["authorization"].to_str().unwrap(),
+      expected[4].1.as_str(),
+    );
+
+    let mut request = reqwest::Client::new().get(url.clone()).build().unwrap();
+    request
+      .sign_s3_with(options.set_auth_mode(AuthMode::Anonymous))
+      .unwrap();
+    assert!(!request.headers().contains_key("authorization"));
+    assert!(!request.headers().contains_key("x-amz-request-payer"));
+    assert::equal(
+      request.headers()["host"].to_str().unwrap(),
+      "examplebucket.s3.amazonaws.com",
+    );
+  }
+
+  #[cfg(feature = "reqwest-blocking")]
+  #[test]
+  fn sign_s3_blocking_hashes_body() {
+    let mut request = reqwest::blocking::Client::new()
+      .put("https://examplebucket.s3.amazonaws.com/test.txt")
+      .body("abc")
+      .build()
+      .unwrap();
+    request.sign_s3(&get_credentials(), "us-east-1").unwrap();
+
+    assert::equal(
+      request.headers()["x-amz-content-sha256"].to_str().unwrap(),
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+    );
+  }
+}
@@ -0,0 +1,571 @@
+//! Deciding when to retry S3 requests, and how long to wait.
+//!
+//! `RetryClassifier` looks at a response, `RetryPolicy` picks the delay with
+//! exponential backoff and full jitter, and `RetryQuota` stops retry storms
+//! when a service is struggling. Each attempt must be signed again, as
+//! `x-amz-date` has to change.
+//!
+use crate::aws_format;
+use core::time::Duration;
+
+/// Why a response can be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetryKind {
+  /// The service asked us to slow down, such as `SlowDown` or 429.
+  Throttling,
+  /// A server-side failure, such as `InternalError`, 500 or 503.
+  Transient,
+  /// The request took too long, such as `RequestTimeout`.
+  Timeout,
+  /// The request time was too far from the server's. Correct the clock,
+  /// such as with an `OffsetClock`, before retrying.
+  ClockSkew,
+}
+
+const THROTTLING_CODES: [&str; 8] = [
+  "SlowDown",
+  "Throttling",
+  "ThrottlingException",
+  "ThrottledException",
+  "RequestThrottled",
+  "RequestThrottledException",
+  "TooManyRequestsException",
+  "RequestLimitExceeded",
+];
+const TRANSIENT_CODES: [&str; 4] = [
+  "InternalError",
+  "ServiceUnavailable",
+  "PriorRequestNotComplete",
+  "TransientError",
+];
+const TIMEOUT_CODES: [&str; 2] = ["RequestTimeout", "RequestTimeoutException"];
+const CLOCK_SKEW_CODES: [&str; 3] = ["RequestTimeTooSkewed", "RequestExpired", "RequestInTheFuture"];
+
+/// Decides whether a response can be retried, from the status code, the error
+/// code and the headers. It works with any HTTP client.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{RetryClassifier, RetryKind};
+///
+/// let classifier = RetryClassifier::new();
+/// let no_headers: [(&str, &str); 0] = [];
+///
+/// let body = "<Error><Code>SlowDown</Code><Message>Please reduce your request rate.</Message></Error>";
+/// assert_eq!(classifier.classify(503, &no_headers, body), Some(RetryKind::Throttling));
+///
+/// // HEAD responses have no body, so only the status code is known.
+/// assert_eq!(classifier.classify(500, &no_headers, ""), Some(RetryKind::Transient));
+///
+/// let body = "<Error><Code>NoSuchKey</Code></Error>";
+/// assert_eq!(classifier.classify(404, &no_headers, body), None);
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/API/ErrorResponses.html
+/// * https://docs.aws.amazon.com/sdkref/latest/guide/feature-retry-behavior.html
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryClassifier {
+  /// Whether `RetryKind::ClockSkew` is retryable. Only useful when the clock
+  /// is corrected between attempts. Defaults to true.
+  pub retry_clock_skew: bool,
+}
+
+impl Default for RetryClassifier {
+  fn default() -> Self {
+    Self { retry_clock_skew: true }
+  }
+}
+
+impl RetryClassifier {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn set_retry_clock_skew(mut self, value: bool) -> Self {
+    self.retry_clock_skew = value;
+    self
+  }
+
+  /// Classifies a response. Returns `None` for successes and for errors that
+  /// will fail again.
+  ///
+  /// S3 can answer CopyObject and CompleteMultipartUpload with 200 OK and an
+  /// error document, so the body is checked for any status.
+  pub fn classify<K: AsRef<str>, V: AsRef<str>>(
+    &self,
+    status: u16,
+    headers: &[(K, V)],
+    body: &str,
+  ) -> Option<RetryKind> {
+    if let Some(kind) = get_error_code(headers, body).and_then(|code| self.classify_code(code)) {
+      return Some(kind);
+    }
+    match status {
+      429 => Some(RetryKind::Throttling),
+      500 | 502 | 503 | 504 => Some(RetryKind::Transient),
+      _ => None,
+    }
+  }
+
+  /// Classifies an S3 error code, such as `SlowDown`.
+  pub fn classify_code(&self, code: &str) -> Option<RetryKind> {
+    if THROTTLING_CODES.contains(&code) {
+      Some(RetryKind::Throttling)
+    } else if TRANSIENT_CODES.contains(&code) {
+      Some(RetryKind::Transient)
+    } else if TIMEOUT_CODES.contains(&code) {
+      Some(RetryKind::Timeout)
+    } else if CLOCK_SKEW_CODES.contains(&code) && self.retry_clock_skew {
+      Some(RetryKind::ClockSkew)
+    } else {
+      None
+    }
+  }
+}
+
+/// Gets the error code from the `x-amz-error-code` header, or else from the
+/// `Code` of an error document.
+pub fn get_error_code<'a, K: AsRef<str>, V: AsRef<str>>(headers: &'a [(K, V)], body: &'a str) -> Option<&'a str> {
+  headers
+    .iter()
+    .find(|(k, _)| k.as_ref().eq_ignore_ascii_case("x-amz-error-code"))
+    .map(|(_, v)| v.as_ref().trim())
+    .or_else(|| {
+      let start = body.find("<Error>")?;
+      aws_format::get_element_text(&body[start..], "Code")
+    })
+    .filter(|code| !code.is_empty())
+}
+
+/// Gets the delay from a `Retry-After` header in seconds. HTTP dates are
+/// ignored, as the local clock may be wrong.
+pub fn get_retry_after<K: AsRef<str>, V: AsRef<str>>(headers: &[(K, V)]) -> Option<Duration> {
+  headers
+    .iter()
+    .find(|(k, _)| k.as_ref().eq_ignore_ascii_case("retry-after"))
+    .and_then(|(_, v)| v.as_ref().trim().parse::<u64>().ok())
+    .map(Duration::from_secs)
+}
+
+/// A token bucket shared by all requests to a service. Retries spend tokens
+/// and successes put them back, so when most requests fail, retries stop
+/// instead of adding load. The costs match the AWS SDKs.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{RetryKind, RetryQuota};
+///
+/// let mut quota = RetryQuota::new(10);
+/// assert_eq!(quota.acquire(RetryKind::Transient), Some(5));
+/// assert_eq!(quota.acquire(RetryKind::Timeout), None);
+///
+/// // The retry worked, so give its tokens back.
+/// quota.release(5);
+/// assert_eq!(quota.available(), 10);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryQuota {
+  capacity: u32,
+  available: u32,
+}
+
+impl Default for RetryQuota {
+  fn default() -> Self {
+    Self::new(Self::DEFAULT_CAPACITY)
+  }
+}
+
+impl RetryQuota {
+  pub const DEFAULT_CAPACITY: u32 = 500;
+  pub const RETRY_COST: u32 = 5;
+  pub const TIMEOUT_RETRY_COST: u32 = 10;
+  /// Added for each request that succeeds the first time.
+  pub const SUCCESS_INCREMENT: u32 = 1;
+
+  pub fn new(capacity: u32) -> Self {
+    Self {
+      capacity,
+      available: capacity,
+    }
+  }
+
+  pub fn capacity(&self) -> u32 {
+    self.capacity
+  }
+
+  pub fn available(&self) -> u32 {
+    self.available
+  }
+
+  /// Takes the tokens for a retry. Returns the cost, or `None` if the quota
+  /// is used up and the request should fail now.
+  pub fn acquire(&mut self, kind: RetryKind) -> Option<u32> {
+    let cost = Self::cost(kind);
+    self.available = self.available.checked_sub(cost)?;
+    Some(cost)
+  }
+
+  /// Gets the tokens a retry of this kind costs.
+  pub fn cost(kind: RetryKind) -> u32 {
+    match kind {
+      RetryKind::Timeout => Self::TIMEOUT_RETRY_COST,
+      _ => Self::RETRY_COST,
+    }
+  }
+
+  /// Gives back the tokens of a retry that succeeded.
+  pub fn release(&mut self, cost: u32) {
+    self.available = self.available.saturating_add(cost).min(self.capacity);
+  }
+
+  /// Records a request that succeeded without a retry.
+  pub fn record_success(&mut self) {
+    self.release(Self::SUCCESS_INCREMENT);
+  }
+}
+
+/// Exponential backoff with full jitter: before retry n, wait a random time
+/// between zero and `base_delay * 2^(n - 1)`, capped at `max_delay`.
+/// Throttling starts from the longer `throttling_base_delay`.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{RetryKind, RetryPolicy, RetryQuota};
+/// use std::time::Duration;
+///
+/// let mut policy = RetryPolicy::new().set_max_attempts(3).set_seed(42);
+/// let mut quota = RetryQuota::default();
+///
+/// // After the first attempt fails, wait up to 100ms.
+/// let delay = policy.next_delay(1, RetryKind::Transient, &mut quota).unwrap();
+/// assert!(delay <= Duration::from_millis(100));
+///
+/// // Give up after the third attempt.
+/// assert_eq!(policy.next_delay(3, RetryKind::Transient, &mut quota), None);
+/// ```
+///
+/// # See
+///
+/// * https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+  /// Attempts in total, including the first.
+  pub max_attempts: u32,
+  pub base_delay: Duration,
+  pub throttling_base_delay: Duration,
+  pub max_delay: Duration,
+  seed: u64,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      max_attempts: 3,
+      base_delay: Duration::from_millis(100),
+      throttling_base_delay: Duration::from_millis(500),
+      max_delay: Duration::from_secs(20),
+      seed: default_seed(),
+    }
+  }
+}
+
+impl RetryPolicy {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn set_max_attempts(mut self, value: u32) -> Self {
+    self.max_attempts = value;
+    self
+  }
+
+  pub fn set_base_delay(mut self, value: Duration) -> Self {
+    self.base_delay = value;
+    self
+  }
+
+  pub fn set_throttling_base_delay(mut self, value: Duration) -> Self {
+    self.throttling_base_delay = value;
+    self
+  }
+
+  pub fn set_max_delay(mut self, value: Duration) -> Self {
+    self.max_delay = value;
+    self
+  }
+
+  /// Seeds the jitter. Without `std` the default seed is fixed, so set one
+  /// per client to keep clients from retrying in step.
+  pub fn set_seed(mut self, value: u64) -> Self {
+    self.seed = value | 1;
+    self
+  }
+
+  /// Gets the longest delay before the retry that follows `attempt`, which
+  /// counts from 1.
+  pub fn max_delay_for(&self, attempt: u32, kind: RetryKind) -> Duration {
+    let base = match kind {
+      RetryKind::Throttling => self.throttling_base_delay,
+      _ => self.base_delay,
+    };
+    let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+    base.saturating_mul(factor).min(self.max_delay)
+  }
+
+  /// Gets the delay before the retry that follows `attempt`, with `random`
+  /// from 0.0 to 1.0 as the jitter.
+  pub fn delay_for(&self, attempt: u32, kind: RetryKind, random: f64) -> Duration {
+    self.max_delay_for(attempt, kind).mul_f64(random.clamp(0.0, 1.0))
+  }
+
+  /// Decides whether to retry after `attempt` failed, taking tokens from the
+  /// quota. Returns how long to wait, or `None` to give up.
+  pub fn next_delay(&mut self, attempt: u32, kind: RetryKind, quota: &mut RetryQuota) -> Option<Duration> {
+    if attempt >= self.max_attempts {
+      return None;
+    }
+    quota.acquire(kind)?;
+    let random = self.next_random();
+    Some(self.delay_for(attempt, kind, random))
+  }
+
+  /// xorshift64*, which is plenty for jitter.
+  fn next_random(&mut self) -> f64 {
+    let mut x = self.seed;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    self.seed = x;
+    (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+  }
+}
+
+#[cfg(feature = "std")]
+fn default_seed() -> u64 {
+  let nanos = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|d| d.as_nanos() as u64)
+    .unwrap_or_default();
+  nanos | 1
+}
+
+#[cfg(not(feature = "std"))]
+fn default_seed() -> u64 {
+  0x9E37_79B9_7F4A_7C15
+}
+
+/// Calls `attempt` until it succeeds, fails in a way that can't be retried,
+/// or the policy gives up, sleeping between attempts. `attempt` gets the
+/// attempt number, from 1, and is the hook to sign the request again so each
+/// attempt has a fresh `x-amz-date`. It returns `Ok` on success, or the error
+/// and how it can be retried, if at all. Only successes refill the quota.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{retry, RetryClassifier, RetryPolicy, RetryQuota, S3DateTime, S3HeadersBuilder};
+/// use std::time::Duration;
+/// use url::Url;
+///
+/// let url = Url::parse("https://jsonlog.s3.amazonaws.com/test.json").unwrap();
+/// let classifier = RetryClassifier::new();
+/// let mut policy = RetryPolicy::new().set_base_delay(Duration::from_millis(1));
+/// let mut quota = RetryQuota::default();
+///
+/// let status = retry(&mut policy, &mut quota, |attempt| {
+///   // Sign again for every attempt.
+///   let _headers = S3HeadersBuilder::new(&url).set_method("GET").build();
+///   // Pretend the first attempt was throttled.
+///   let (status, body) = if attempt == 1 { (503, "<Error><Code>SlowDown</Code></Error>") } else { (200, "") };
+///   let no_headers: [(&str, &str); 0] = [];
+///   match status {
+///     200..=299 => Ok(status),
+///     _ => Err((status, classifier.classify(status, &no_headers, body))),
+///   }
+/// });
+/// assert_eq!(status, Ok(200));
+/// ```
+///
+#[cfg(feature = "std")]
+pub fn retry<T, E, F: FnMut(u32) -> Result<T, (E, Option<RetryKind>)>>(
+  policy: &mut RetryPolicy,
+  quota: &mut RetryQuota,
+  mut attempt: F,
+) -> Result<T, E> {
+  let mut number = 1;
+  let mut last_cost = None;
+  loop {
+    let (error, kind) = match attempt(number) {
+      Ok(result) => {
+        match last_cost {
+          Some(cost) => quota.release(cost),
+          None => quota.record_success(),
+        }
+        return Ok(result);
+      }
+      Err(error) => error,
+    };
+    let Some(kind) = kind else {
+      return Err(error);
+    };
+    match policy.next_delay(number, kind, quota) {
+      Some(delay) => std::thread::sleep(delay),
+      None => return Err(error),
+    }
+    last_cost = Some(RetryQuota::cost(kind));
+    number += 1;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common_testing::assert;
+
+  const NO_HEADERS: [(&str, &str); 0] = [];
+
+  #[test]
+  fn classify_reads_error_in_200_response() {
+    let body = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+      <Error><Code>InternalError</Code><Message>We encountered an internal error. Please try again.</Message></Error>";
+    assert::equal(
+      RetryClassifier::new().classify(200, &NO_HEADERS, body),
+      Some(RetryKind::Transient),
+    );
+  }
+
+  #[test]
+  fn classify_ignores_code_elements_outside_errors() {
+    let body = "<CopyObjectResult><Code>SlowDown</Code></CopyObjectResult>";
+    assert::none(&RetryClassifier::new().classify(200, &NO_HEADERS, body));
+  }
+
+  #[test]
+  fn classify_clock_skew_can_be_disabled() {
+    let body = "<Error><Code>RequestTimeTooSkewed</Code></Error>";
+    assert::equal(
+      RetryClassifier::new().classify(403, &NO_HEADERS, body),
+      Some(RetryKind::ClockSkew),
+    );
+    assert::none(
+      &RetryClassifier::new()
+        .set_retry_clock_skew(false)
+        .classify(403, &NO_HEADERS, body),
+    );
+  }
+
+  #[test]
+  fn classify_uses_error_code_header() {
+    let headers = [("x-amz-error-code", "RequestTimeout")];
+    assert::equal(
+      RetryClassifier::new().classify(400, &headers, ""),
+      Some(RetryKind::Timeout),
+    );
+  }
+
+  #[test]
+  fn get_retry_after_reads_seconds() {
+    assert::equal(get_retry_after(&[("Retry-After", "3")]), Some(Duration::from_secs(3)));
+    assert::none(&get_retry_after(&[("Retry-After", "Wed, 21 Oct 2015 07:28:00 GMT")]));
+  }
+
+  #[test]
+  fn max_delay_doubles_up_to_cap() {
+    let policy = RetryPolicy::new().set_max_delay(Duration::from_millis(350));
+    assert::equal(
+      policy.max_delay_for(1, RetryKind::Transient),
+      Duration::from_millis(100),
+    );
+    assert::equal(
+      policy.max_delay_for(2, RetryKind::Transient),
+      Duration::from_millis(200),
+    );
+    assert::equal(
+      policy.max_delay_for(3, RetryKind::Transient),
+      Duration::from_millis(350),
+    );
+    assert::equal(
+      policy.max_delay_for(100, RetryKind::Throttling),
+      Duration::from_millis(350),
+    );
+  }
+
+  #[test]
+  fn jitter_stays_in_range() {
+    let mut policy = RetryPolicy::new().set_max_attempts(u32::MAX).set_seed(7);
+    let mut quota = RetryQuota::new(u32::MAX);
+    for attempt in 1..50 {
+      let delay = policy.next_delay(attempt, RetryKind::Transient, &mut quota).unwrap();
+      assert!(delay <= policy.max_delay_for(attempt, RetryKind::Transient));
+    }
+  }
+
+  #[test]
+  fn quota_release_saturates_at_max_capacity() {
+    let mut quota = RetryQuota::new(u32::MAX);
+    quota.record_success();
+    assert::equal(quota.available(), u32::MAX);
+    quota.release(RetryQuota::TIMEOUT_RETRY_COST);
+    assert::equal(quota.available(), u32::MAX);
+  }
+
+  #[test]
+  fn quota_stops_retries() {
+    let mut policy = RetryPolicy::new().set_max_attempts(10);
+    let mut quota = RetryQuota::new(10);
+    assert::some(&policy.next_delay(1, RetryKind::Transient, &mut quota));
+    assert::some(&policy.next_delay(2, RetryKind::Transient, &mut quota));
+    assert::none(&policy.next_delay(3, RetryKind::Transient, &mut quota));
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn retry_gives_up_and_returns_last_result() {
+    let mut policy = RetryPolicy::new().set_base_delay(Duration::ZERO);
+    let mut quota = RetryQuota::default();
+    let mut attempts = Vec::new();
+
+    let result = retry(&mut policy, &mut quota, |attempt| {
+      attempts.push(attempt);
+      Err::<(), _>((attempt, Some(RetryKind::Transient)))
+    });
+
+    assert::equal(result, Err(3));
+    assert::equal(attempts, vec![1, 2, 3]);
+    assert::equal(quota.available(), 490);
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn retry_only_refills_quota_on_success() {
+    let mut policy = RetryPolicy::new().set_base_delay(Duration::ZERO);
+    let mut quota = RetryQuota::new(10);
+    quota.acquire(RetryKind::Transient);
+
+    // A retry that ends in an error that can't be retried keeps its tokens.
+    let result = retry(&mut policy, &mut quota, |attempt| match attempt {
+      1 => Err::<u32, _>((503, Some(RetryKind::Transient))),
+      _ => Err((403, None)),
+    });
+    assert::equal(result, Err(403));
+    assert::equal(quota.available(), 0);
+
+    // So does a first attempt that can't be retried.
+    let mut quota = RetryQuota::new(10);
+    quota.acquire(RetryKind::Transient);
+    assert::equal(retry(&mut policy, &mut quota, |_| Err::<u32, _>((404, None))), Err(404));
+    assert::equal(quota.available(), 5);
+
+    // Successes give back a retry's tokens, or add the success increment.
+    assert::equal(retry(&mut policy, &mut quota, |_| Ok::<_, (u32, _)>(200)), Ok(200));
+    assert::equal(quota.available(), 6);
+  }
+}
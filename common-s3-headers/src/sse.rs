@@ -0,0 +1,349 @@
+//! Server-side encryption headers.
+//!
+use crate::aws_format;
+use crate::prelude::*;
+use crate::Headers;
+use alloc::collections::BTreeMap;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use md5::{Digest, Md5};
+use zeroize::{Zeroize, Zeroizing};
+
+/// The only algorithm S3 accepts for customer-provided keys.
+pub const SSE_CUSTOMER_ALGORITHM: &str = "AES256";
+
+/// The algorithms S3 can use to encrypt an object at rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SseAlgorithm {
+  /// SSE-S3, keys managed by S3.
+  Aes256,
+  /// SSE-KMS, keys managed by AWS KMS.
+  AwsKms,
+  /// DSSE-KMS, dual-layer encryption with keys managed by AWS KMS.
+  AwsKmsDsse,
+}
+
+impl SseAlgorithm {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      SseAlgorithm::Aes256 => "AES256",
+      SseAlgorithm::AwsKms => "aws:kms",
+      SseAlgorithm::AwsKmsDsse => "aws:kms:dsse",
+    }
+  }
+}
+
+impl core::fmt::Display for SseAlgorithm {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// Server-side encryption with keys managed by S3 or KMS (SSE-S3, SSE-KMS).
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::ServerSideEncryption;
+///
+/// let headers = ServerSideEncryption::aws_kms()
+///   .set_kms_key_id("arn:aws:kms:us-east-1:111122223333:key/example")
+///   .set_context("department", "finance")
+///   .set_bucket_key_enabled(true)
+///   .to_headers();
+///
+/// assert_eq!(headers, vec![
+///   ("x-amz-server-side-encryption", "aws:kms".to_owned()),
+///   ("x-amz-server-side-encryption-aws-kms-key-id", "arn:aws:kms:us-east-1:111122223333:key/example".to_owned()),
+///   // {"department":"finance"}
+///   ("x-amz-server-side-encryption-context", "eyJkZXBhcnRtZW50IjoiZmluYW5jZSJ9".to_owned()),
+///   ("x-amz-server-side-encryption-bucket-key-enabled", "true".to_owned()),
+/// ]);
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/userguide/specifying-kms-encryption.html
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerSideEncryption {
+  pub algorithm: SseAlgorithm,
+  pub kms_key_id: Option<String>,
+  pub context: BTreeMap<String, String>,
+  pub bucket_key_enabled: Option<bool>,
+}
+
+impl ServerSideEncryption {
+  pub fn new(algorithm: SseAlgorithm) -> Self {
+    Self {
+      algorithm,
+      kms_key_id: None,
+      context: BTreeMap::new(),
+      bucket_key_enabled: None,
+    }
+  }
+
+  /// SSE-S3.
+  pub fn aes256() -> Self {
+    Self::new(SseAlgorithm::Aes256)
+  }
+
+  /// SSE-KMS. Without a key id, S3 uses the AWS managed key.
+  pub fn aws_kms() -> Self {
+    Self::new(SseAlgorithm::AwsKms)
+  }
+
+  pub fn set_kms_key_id<S: Into<String>>(mut self, value: S) -> Self {
+    self.kms_key_id = Some(value.into());
+    self
+  }
+
+  /// Adds a key-value pair to the KMS encryption context.
+  pub fn set_context<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+    self.context.insert(key.into(), value.into());
+    self
+  }
+
+  pub fn set_bucket_key_enabled(mut self, value: bool) -> Self {
+    self.bucket_key_enabled = Some(value);
+    self
+  }
+
+  /// Gets the headers for a PUT, POST, copy or multipart upload.
+  pub fn to_headers(&self) -> Vec<(&'static str, String)> {
+    let mut headers = vec![("x-amz-server-side-encryption", self.algorithm.as_str().to_owned())];
+
+    if let Some(kms_key_id) = &self.kms_key_id {
+      headers.push(("x-amz-server-side-encryption-aws-kms-key-id", kms_key_id.clone()));
+    }
+
+    if !self.context.is_empty() {
+      headers.push((
+        "x-amz-server-side-encryption-context",
+        BASE64.encode(aws_format::json_object_string(&self.context)),
+      ));
+    }
+
+    if let Some(bucket_key_enabled) = self.bucket_key_enabled {
+      headers.push((
+        "x-amz-server-side-encryption-bucket-key-enabled",
+        bucket_key_enabled.to_string(),
+      ));
+    }
+
+    headers
+  }
+}
+
+/// A customer-provided 256-bit encryption key (SSE-C). The key is zeroized
+/// when dropped and never shown by `Debug`. Its headers come as `Headers`,
+/// which redacts and zeroizes them too.
+///
+/// All headers produced start with `x-amz-`, so they are always part of the
+/// signed headers.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::SseCustomerKey;
+///
+/// let key = SseCustomerKey::new([0u8; 32]);
+/// let headers = key.to_headers();
+/// assert_eq!(headers.get("x-amz-server-side-encryption-customer-algorithm"), Some("AES256"));
+/// assert_eq!(format!("{:?}", key), "SseCustomerKey { key: \"<redacted>\" }");
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/userguide/ServerSideEncryptionCustomerKeys.html
+///
+pub struct SseCustomerKey {
+  key: [u8; 32],
+}
+
+impl SseCustomerKey {
+  pub fn new(key: [u8; 32]) -> Self {
+    Self { key }
+  }
+
+  /// The key, base64 encoded.
+  fn key_base64(&self) -> Zeroizing<String> {
+    Zeroizing::new(BASE64.encode(self.key))
+  }
+
+  /// The MD5 digest of the key, base64 encoded. S3 uses it as an integrity check.
+  fn key_md5_base64(&self) -> Zeroizing<String> {
+    Zeroizing::new(BASE64.encode(Md5::digest(self.key)))
+  }
+
+  /// Gets the headers to use this key for a GET, HEAD, PUT or multipart upload.
+  pub fn to_headers(&self) -> Headers {
+    let mut headers = Headers::with_capacity(3);
+    headers.insert(
+      "x-amz-server-side-encryption-customer-algorithm",
+      SSE_CUSTOMER_ALGORITHM,
+    );
+    headers.insert("x-amz-server-side-encryption-customer-key", self.key_base64().as_str());
+    headers.insert(
+      "x-amz-server-side-encryption-customer-key-MD5",
+      self.key_md5_base64().as_str(),
+    );
+    headers
+  }
+
+  /// Gets the headers to decrypt the source object of a copy.
+  pub fn to_copy_source_headers(&self) -> Headers {
+    let mut headers = Headers::with_capacity(3);
+    headers.insert(
+      "x-amz-copy-source-server-side-encryption-customer-algorithm",
+      SSE_CUSTOMER_ALGORITHM,
+    );
+    headers.insert(
+      "x-amz-copy-source-server-side-encryption-customer-key",
+      self.key_base64().as_str(),
+    );
+    headers.insert(
+      "x-amz-copy-source-server-side-encryption-customer-key-MD5",
+      self.key_md5_base64().as_str(),
+    );
+    headers
+  }
+}
+
+impl From<[u8; 32]> for SseCustomerKey {
+  fn from(key: [u8; 32]) -> Self {
+    Self::new(key)
+  }
+}
+
+impl TryFrom<&[u8]> for SseCustomerKey {
+  type Error = core::array::TryFromSliceError;
+
+  fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+    Ok(Self::new(value.try_into()?))
+  }
+}
+
+impl core::fmt::Debug for SseCustomerKey {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("SseCustomerKey").field("key", &"<redacted>").finish()
+  }
+}
+
+impl Drop for SseCustomerKey {
+  fn drop(&mut self) {
+    self.key.zeroize();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::aws_canonical::to_canonical_headers;
+  use common_testing::assert;
+
+  fn get_key() -> SseCustomerKey {
+    let mut key = [0u8; 32];
+    key.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+    SseCustomerKey::new(key)
+  }
+
+  #[test]
+  fn to_headers_encodes_key_and_md5() {
+    assert::equal(
+      get_key().to_headers(),
+      Headers::from(vec![
+        ("x-amz-server-side-encryption-customer-algorithm", "AES256".to_owned()),
+        (
+          "x-amz-server-side-encryption-customer-key",
+          "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=".to_owned(),
+        ),
+        (
+          "x-amz-server-side-encryption-customer-key-MD5",
+          "tP/LI3N87DFaSk0aoqYgzg==".to_owned(),
+        ),
+      ]),
+    );
+  }
+
+  #[test]
+  fn to_copy_source_headers_encodes_key_and_md5() {
+    assert::equal(
+      get_key().to_copy_source_headers(),
+      Headers::from(vec![
+        (
+          "x-amz-copy-source-server-side-encryption-customer-algorithm",
+          "AES256".to_owned(),
+        ),
+        (
+          "x-amz-copy-source-server-side-encryption-customer-key",
+          "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=".to_owned(),
+        ),
+        (
+          "x-amz-copy-source-server-side-encryption-customer-key-MD5",
+          "tP/LI3N87DFaSk0aoqYgzg==".to_owned(),
+        ),
+      ]),
+    );
+  }
+
+  #[test]
+  fn headers_are_signed() {
+    let headers = get_key().to_headers();
+    let canonical_headers = to_canonical_headers(headers.as_slice());
+    assert::equal(canonical_headers.len(), 3);
+    assert::equal(
+      canonical_headers[2].0.as_str(),
+      "x-amz-server-side-encryption-customer-key-md5",
+    );
+  }
+
+  #[test]
+  fn debug_is_redacted() {
+    let result = format!("{:?}", get_key());
+    assert::equal(result.as_str(), "SseCustomerKey { key: \"<redacted>\" }");
+  }
+
+  #[test]
+  fn builder_debug_redacts_key_headers() {
+    let url = url::Url::parse("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+    let builder = crate::S3HeadersBuilder::new(&url).add_headers(get_key().to_headers());
+    let result = format!("{:?}", builder);
+
+    assert!(!result.contains(get_key().key_base64().as_str()));
+    assert!(!result.contains(get_key().key_md5_base64().as_str()));
+    assert!(result.contains("(\"x-amz-server-side-encryption-customer-key\", \"<redacted>\")"));
+  }
+
+  #[test]
+  fn server_side_encryption_aes256() {
+    assert::equal(
+      ServerSideEncryption::aes256().to_headers(),
+      vec![("x-amz-server-side-encryption", "AES256".to_owned())],
+    );
+  }
+
+  #[test]
+  fn server_side_encryption_context_is_sorted_base64_json() {
+    let headers = ServerSideEncryption::aws_kms()
+      .set_context("b", "2")
+      .set_context("a", "say \"hi\"")
+      .to_headers();
+
+    assert::equal(
+      headers,
+      vec![
+        ("x-amz-server-side-encryption", "aws:kms".to_owned()),
+        (
+          "x-amz-server-side-encryption-context",
+          BASE64.encode(r#"{"a":"say \"hi\"","b":"2"}"#),
+        ),
+      ],
+    );
+  }
+
+  #[test]
+  fn try_from_rejects_wrong_length() {
+    let result = SseCustomerKey::try_from([0u8; 16].as_slice());
+    assert::err(&result);
+  }
+}
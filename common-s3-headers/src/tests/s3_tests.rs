@@ -1,6 +1,12 @@
 use crate::{
+  aws_canonical::HeaderPolicy,
   aws_math::get_sha256,
-  s3::{self, S3DateTime, S3HeadersBuilder},
+  clock::{FixedClock, OffsetClock},
+  conditions::Conditions,
+  metadata::UserMetadata,
+  object::StorageClass,
+  s3::{self, AuthMode, S3DateTime, S3HeadersBuilder},
+  sse::{ServerSideEncryption, SseCustomerKey},
 };
 use common_testing::assert;
 use std::str::FromStr;
@@ -57,3 +63,227 @@ fn test_put_object() {
     )
   ])
 }
+
+#[test]
+fn test_put_object_with_fixed_clock() {
+  let url = Url::from_str("https://examplebucket.s3.amazonaws.com/test$file.text").unwrap();
+  let headers = &[("x-amz-storage-class", "REDUCED_REDUNDANCY".to_owned())];
+  let clock = FixedClock::from_unix_timestamp(1369324800);
+  let options = S3HeadersBuilder::new(&url)
+    .set_access_key("some_access_key")
+    .set_secret_key("some_secret_key")
+    .set_region("some_place")
+    .set_clock(&clock)
+    .set_headers(headers)
+    .set_method("PUT")
+    .set_service("s3");
+  let result = s3::get_headers(options);
+
+  assert::equal(
+    result[4].1.as_str(),
+    "AWS4-HMAC-SHA256 Credential=some_access_key/20130523/some_place/s3/aws4_request,SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-storage-class,Signature=7e2911c8225f7591609bcbdc2faf8c443a898d8c83fc35b6a23f0b0e8084da60",
+  );
+}
+
+#[test]
+fn test_clock_is_read_when_signing() {
+  let url = Url::from_str("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+  let clock = OffsetClock::new();
+  let options = S3HeadersBuilder::new(&url)
+    .set_access_key("some_access_key")
+    .set_secret_key("some_secret_key")
+    .set_region("some_place")
+    .set_clock(&clock)
+    .set_method("GET")
+    .set_service("s3");
+  // About a hundred years ahead, corrected after the clock was set.
+  clock.set_offset_seconds(100 * 365 * 24 * 60 * 60);
+  let result = s3::get_headers(options);
+
+  assert::equal(result[2].0, "x-amz-date");
+  assert::equal(result[2].1.starts_with("21"), true);
+}
+
+#[test]
+fn test_put_object_with_sse_customer_key() {
+  let url = Url::from_str("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+  let headers = SseCustomerKey::new([0u8; 32]).to_headers();
+  let options = S3HeadersBuilder::new(&url)
+    .set_access_key("some_access_key")
+    .set_secret_key("some_secret_key")
+    .set_region("some_place")
+    .set_datetime(S3DateTime::UnixTimestamp(0))
+    .set_headers(headers)
+    .set_method("PUT")
+    .set_service("s3");
+  let result = s3::get_headers(options);
+  let (_, authorization) = result.last().unwrap();
+
+  assert!(authorization.contains(
+    "SignedHeaders=host;x-amz-content-sha256;x-amz-date;\
+    x-amz-server-side-encryption-customer-algorithm;\
+    x-amz-server-side-encryption-customer-key;\
+    x-amz-server-side-encryption-customer-key-md5,"
+  ));
+}
+
+#[test]
+fn test_put_object_with_typed_headers() {
+  let url = Url::from_str("https://examplebucket.s3.amazonaws.com/test$file.text").unwrap();
+  let headers = &[StorageClass::ReducedRedundancy.to_header()];
+  let content = b"".as_slice();
+  let sha = get_sha256(content);
+  let options = S3HeadersBuilder::new(&url)
+    .set_access_key("some_access_key")
+    .set_secret_key("some_secret_key")
+    .set_region("some_place")
+    .set_datetime(S3DateTime::UnixTimestamp(1369324800)) // 20130524T000000Z
+    .set_headers(headers)
+    .set_method("PUT")
+    .set_service("s3")
+    .set_payload_hash(&sha);
+  let result = s3::get_headers(options);
+
+  // Should be exactly the same as the raw string tuple in `test_put_object`.
+  let (_, authorization) = result.last().unwrap();
+  assert::equal(
+    authorization.as_str(),
+    "AWS4-HMAC-SHA256 Credential=some_access_key/20130523/some_place/s3/aws4_request,SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-storage-class,Signature=7e2911c8225f7591609bcbdc2faf8c443a898d8c83fc35b6a23f0b0e8084da60",
+  );
+}
+
+#[test]
+fn test_put_object_with_server_side_encryption() {
+  let url = Url::from_str("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+  let headers = ServerSideEncryption::aws_kms()
+    .set_bucket_key_enabled(true)
+    .to_headers();
+  let options = S3HeadersBuilder::new(&url)
+    .set_access_key("some_access_key")
+    .set_secret_key("some_secret_key")
+    .set_region("some_place")
+    .set_datetime(S3DateTime::UnixTimestamp(0))
+    .set_headers(&headers)
+    .set_method("PUT")
+    .set_service("s3");
+  let result = s3::get_headers(options);
+  let (_, authorization) = result.last().unwrap();
+
+  assert!(authorization.contains(
+    "SignedHeaders=host;x-amz-content-sha256;x-amz-date;\
+    x-amz-server-side-encryption;x-amz-server-side-encryption-bucket-key-enabled,"
+  ));
+}
+
+#[test]
+fn test_put_object_if_none_match_is_signed_with_all_headers() {
+  let url = Url::from_str("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+  let headers = Conditions::new().set_if_none_match_any().to_headers();
+  let options = S3HeadersBuilder::new(&url)
+    .set_access_key("some_access_key")
+    .set_secret_key("some_secret_key")
+    .set_region("some_place")
+    .set_datetime(S3DateTime::UnixTimestamp(0))
+    .set_headers(&headers)
+    .set_method("PUT")
+    .set_service("s3");
+
+  let minimal = s3::get_headers(options.clone());
+  let all = s3::get_headers(options.set_header_policy(HeaderPolicy::All));
+
+  assert::equal(minimal[0].clone(), ("If-None-Match", "*".to_owned()));
+  assert!(minimal[4]
+    .1
+    .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date,"));
+  assert!(all[4]
+    .1
+    .contains("SignedHeaders=host;if-none-match;x-amz-content-sha256;x-amz-date,"));
+}
+
+#[test]
+fn test_put_object_with_dynamic_header_names() {
+  let url = Url::from_str("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+  let options = S3HeadersBuilder::new(&url)
+    .set_access_key("some_access_key")
+    .set_secret_key("some_secret_key")
+    .set_region("some_place")
+    .set_datetime(S3DateTime::UnixTimestamp(0))
+    .set_header(format!("x-amz-meta-{}", "author"), "jane")
+    .set_header("host", "ignored.example.com")
+    .set_method("PUT")
+    .set_service("s3");
+  let result = s3::get_signed_headers(options);
+
+  assert::equal(result.len(), 5);
+  assert::equal(result.get("x-amz-meta-author"), Some("jane"));
+  // The generated host replaces the caller's, in place.
+  assert::equal(result.get("Host"), Some("examplebucket.s3.amazonaws.com"));
+  assert!(result
+    .get("Authorization")
+    .unwrap()
+    .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-meta-author,"));
+}
+
+#[test]
+fn test_build_owned_keeps_dynamic_header_names() {
+  let url = Url::from_str("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+  let metadata = UserMetadata::new().set_value("author", "jane").to_headers().unwrap();
+  let result = S3HeadersBuilder::new(&url)
+    .set_access_key("some_access_key")
+    .set_secret_key("some_secret_key")
+    .set_region("some_place")
+    .set_datetime(S3DateTime::UnixTimestamp(0))
+    .add_headers(metadata)
+    .set_method("PUT")
+    .set_service("s3")
+    .build_owned();
+
+  assert::equal(result[0].clone(), ("x-amz-meta-author".into(), "jane".to_owned()));
+  assert!(result[4]
+    .1
+    .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-meta-author,"));
+}
+
+#[test]
+fn test_build_leaves_out_dynamic_header_names() {
+  let url = Url::from_str("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+  let metadata = UserMetadata::new().set_value("author", "jane").to_headers().unwrap();
+  let result = S3HeadersBuilder::new(&url)
+    .set_access_key("some_access_key")
+    .set_secret_key("some_secret_key")
+    .set_region("some_place")
+    .set_datetime(S3DateTime::UnixTimestamp(0))
+    .add_headers(metadata)
+    .set_header("Range", "bytes=0-9")
+    .set_method("PUT")
+    .set_service("s3")
+    .build();
+
+  assert::equal(result[0].clone(), ("Range", "bytes=0-9".to_owned()));
+  assert::equal(result.len(), 5);
+  assert!(result[4]
+    .1
+    .contains("SignedHeaders=host;range;x-amz-content-sha256;x-amz-date,"));
+}
+
+#[test]
+fn test_anonymous_requests_leave_out_requester_pays() {
+  let url = Url::from_str("https://examplebucket.s3.amazonaws.com/test.txt").unwrap();
+  let builder = || {
+    S3HeadersBuilder::new(&url)
+      .set_auth_mode(AuthMode::Anonymous)
+      .set_requester_pays(true)
+      .set_datetime(S3DateTime::UnixTimestamp(0))
+      .set_method("GET")
+  };
+
+  assert!(!builder().sends_requester_pays());
+  let headers = builder().build_headers();
+  assert::equal(headers.get("x-amz-request-payer"), None);
+  assert::equal(headers.get("Authorization"), None);
+  assert::equal(builder().build_presigned_url(60), url.clone());
+
+  let signed = builder().set_auth_mode(AuthMode::Signed);
+  assert!(signed.sends_requester_pays());
+  assert::equal(signed.build_headers().get("x-amz-request-payer"), Some("requester"));
+}
@@ -0,0 +1,351 @@
+//! Checks for bucket names and object keys, before S3 rejects them.
+//!
+use crate::prelude::*;
+use core::fmt;
+
+/// The longest object key, in UTF-8 bytes.
+pub const MAX_OBJECT_KEY_LENGTH: usize = 1024;
+
+const BUCKET_NAME_RESERVED_PREFIXES: [&str; 3] = ["xn--", "sthree-", "amzn-s3-demo-"];
+const BUCKET_NAME_RESERVED_SUFFIXES: [&str; 5] = ["-s3alias", "--ol-s3", ".mrap", "--x-s3", "--table-s3"];
+
+/// Why a bucket name breaks the S3 naming rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidBucketNameError {
+  /// Names are 3 to 63 characters long.
+  Length(usize),
+  /// Only lowercase letters, numbers, periods and hyphens are allowed.
+  InvalidCharacter {
+    index: usize,
+    character: char,
+  },
+  /// Names begin and end with a letter or number.
+  InvalidBoundary {
+    index: usize,
+    character: char,
+  },
+  /// Each label between periods must be non-empty and begin and end with a
+  /// letter or number, so no `..`, `.-` or `-.`.
+  InvalidLabel {
+    index: usize,
+  },
+  /// Names can't look like an IPv4 address, such as `192.168.5.4`.
+  IpAddress,
+  ReservedPrefix(&'static str),
+  ReservedSuffix(&'static str),
+}
+
+impl fmt::Display for InvalidBucketNameError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      InvalidBucketNameError::Length(length) => {
+        write!(f, "bucket name is {} characters, must be 3 to 63", length)
+      }
+      InvalidBucketNameError::InvalidCharacter { index, character } => {
+        write!(
+          f,
+          "bucket name has {:?} at {}, only a-z, 0-9, '.' and '-' are allowed",
+          character, index
+        )
+      }
+      InvalidBucketNameError::InvalidBoundary { index, character } => {
+        write!(
+          f,
+          "bucket name has {:?} at {}, must begin and end with a letter or number",
+          character, index
+        )
+      }
+      InvalidBucketNameError::InvalidLabel { index } => {
+        write!(f, "bucket name has an empty label or a '.' next to a '-' at {}", index)
+      }
+      InvalidBucketNameError::IpAddress => write!(f, "bucket name must not be an IP address"),
+      InvalidBucketNameError::ReservedPrefix(prefix) => write!(f, "bucket name must not start with {:?}", prefix),
+      InvalidBucketNameError::ReservedSuffix(suffix) => write!(f, "bucket name must not end with {:?}", suffix),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidBucketNameError {}
+
+/// A valid bucket name that won't work everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BucketNameWarning {
+  /// Periods break the TLS certificate for virtual-hosted-style HTTPS
+  /// requests, and Transfer Acceleration. Use path-style requests instead.
+  Periods,
+}
+
+/// Checks a general purpose bucket name against the S3 naming rules. Returns
+/// warnings for valid names that only work for some requests.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{validate_bucket_name, BucketNameWarning, InvalidBucketNameError};
+///
+/// assert_eq!(validate_bucket_name("jsonlog"), Ok(vec![]));
+/// assert_eq!(validate_bucket_name("www.example.com"), Ok(vec![BucketNameWarning::Periods]));
+///
+/// assert_eq!(validate_bucket_name("ab"), Err(InvalidBucketNameError::Length(2)));
+/// assert_eq!(validate_bucket_name("192.168.5.4"), Err(InvalidBucketNameError::IpAddress));
+/// assert_eq!(
+///   validate_bucket_name("xn--bucket"),
+///   Err(InvalidBucketNameError::ReservedPrefix("xn--"))
+/// );
+/// assert_eq!(
+///   validate_bucket_name("My_Bucket"),
+///   Err(InvalidBucketNameError::InvalidCharacter { index: 0, character: 'M' })
+/// );
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/userguide/bucketnamingrules.html
+/// * https://docs.aws.amazon.com/AmazonS3/latest/userguide/VirtualHosting.html
+///
+pub fn validate_bucket_name(name: &str) -> Result<Vec<BucketNameWarning>, InvalidBucketNameError> {
+  let length = name.chars().count();
+  if !(3..=63).contains(&length) {
+    return Err(InvalidBucketNameError::Length(length));
+  }
+
+  for (index, character) in name.char_indices() {
+    if !matches!(character, 'a'..='z' | '0'..='9' | '.' | '-') {
+      return Err(InvalidBucketNameError::InvalidCharacter { index, character });
+    }
+  }
+
+  // Only ASCII is left, so byte indexes are character indexes.
+  let bytes = name.as_bytes();
+  for index in [0, bytes.len() - 1] {
+    if !bytes[index].is_ascii_alphanumeric() {
+      return Err(InvalidBucketNameError::InvalidBoundary {
+        index,
+        character: bytes[index] as char,
+      });
+    }
+  }
+  if let Some(index) = bytes.windows(2).position(|pair| matches!(pair, b".." | b".-" | b"-.")) {
+    return Err(InvalidBucketNameError::InvalidLabel { index });
+  }
+
+  if is_ipv4_address(name) {
+    return Err(InvalidBucketNameError::IpAddress);
+  }
+  if let Some(prefix) = BUCKET_NAME_RESERVED_PREFIXES.iter().find(|p| name.starts_with(*p)) {
+    return Err(InvalidBucketNameError::ReservedPrefix(prefix));
+  }
+  if let Some(suffix) = BUCKET_NAME_RESERVED_SUFFIXES.iter().find(|s| name.ends_with(*s)) {
+    return Err(InvalidBucketNameError::ReservedSuffix(suffix));
+  }
+
+  let mut warnings = Vec::new();
+  if name.contains('.') {
+    warnings.push(BucketNameWarning::Periods);
+  }
+  Ok(warnings)
+}
+
+fn is_ipv4_address(name: &str) -> bool {
+  let labels: Vec<&str> = name.split('.').collect();
+  labels.len() == 4
+    && labels
+      .iter()
+      .all(|label| label.bytes().all(|b| b.is_ascii_digit()) && label.parse::<u8>().is_ok())
+}
+
+/// Why S3 will reject an object key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidObjectKeyError {
+  Empty,
+  /// Keys are at most 1024 bytes of UTF-8.
+  TooLong(usize),
+}
+
+impl fmt::Display for InvalidObjectKeyError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      InvalidObjectKeyError::Empty => write!(f, "object key is empty"),
+      InvalidObjectKeyError::TooLong(length) => write!(
+        f,
+        "object key is {} bytes, must be at most {}",
+        length, MAX_OBJECT_KEY_LENGTH
+      ),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidObjectKeyError {}
+
+/// A valid object key that may cause trouble. `index` is the byte index of
+/// the character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKeyWarning {
+  /// Safe, but needs percent encoding in URLs and care in some tools, such
+  /// as `&`, `$`, `@`, `=`, `;`, `:`, `+`, `,`, `?` and space.
+  NeedsSpecialHandling { index: usize, character: char },
+  /// Best avoided, such as `\`, `{`, `^`, `}`, `%`, `` ` ``, `]`, `"`, `>`,
+  /// `[`, `~`, `<`, `#`, `|` and characters above ASCII 127.
+  ShouldAvoid { index: usize, character: char },
+  /// A control character. XML 1.0 can't hold some of them, so list results
+  /// need `encoding-type=url`.
+  ControlCharacter { index: usize, character: char },
+  /// A `.` or `..` path segment. Clients that normalize paths change the key.
+  DotSegment { index: usize },
+}
+
+/// Checks an object key against the S3 limits. Returns warnings for
+/// characters that need special handling or are best avoided.
+///
+/// # Examples
+///
+/// ```
+/// use common_s3_headers::{validate_object_key, InvalidObjectKeyError, ObjectKeyWarning};
+///
+/// assert_eq!(validate_object_key("photos/2006/January/sample.jpg"), Ok(vec![]));
+/// assert_eq!(
+///   validate_object_key("a b"),
+///   Ok(vec![ObjectKeyWarning::NeedsSpecialHandling { index: 1, character: ' ' }])
+/// );
+/// assert_eq!(validate_object_key("a/../b"), Ok(vec![ObjectKeyWarning::DotSegment { index: 2 }]));
+///
+/// assert_eq!(validate_object_key(""), Err(InvalidObjectKeyError::Empty));
+/// assert_eq!(validate_object_key(&"a".repeat(1025)), Err(InvalidObjectKeyError::TooLong(1025)));
+/// ```
+///
+/// # See
+///
+/// * https://docs.aws.amazon.com/AmazonS3/latest/userguide/object-keys.html
+///
+pub fn validate_object_key(key: &str) -> Result<Vec<ObjectKeyWarning>, InvalidObjectKeyError> {
+  if key.is_empty() {
+    return Err(InvalidObjectKeyError::Empty);
+  }
+  if key.len() > MAX_OBJECT_KEY_LENGTH {
+    return Err(InvalidObjectKeyError::TooLong(key.len()));
+  }
+
+  let mut warnings = Vec::new();
+  for (index, character) in key.char_indices() {
+    match character {
+      '\0'..='\x1f' | '\x7f' => warnings.push(ObjectKeyWarning::ControlCharacter { index, character }),
+      '&' | '$' | '@' | '=' | ';' | ':' | '+' | ',' | '?' | ' ' => {
+        warnings.push(ObjectKeyWarning::NeedsSpecialHandling { index, character })
+      }
+      '\\' | '{' | '^' | '}' | '%' | '`' | ']' | '"' | '>' | '[' | '~' | '<' | '#' | '|' => {
+        warnings.push(ObjectKeyWarning::ShouldAvoid { index, character })
+      }
+      c if !c.is_ascii() => warnings.push(ObjectKeyWarning::ShouldAvoid { index, character }),
+      _ => {}
+    }
+  }
+
+  let mut index = 0;
+  for segment in key.split('/') {
+    if segment == "." || segment == ".." {
+      warnings.push(ObjectKeyWarning::DotSegment { index });
+    }
+    index += segment.len() + 1;
+  }
+  warnings.sort_by_key(ObjectKeyWarning::index);
+  Ok(warnings)
+}
+
+impl ObjectKeyWarning {
+  /// The byte index in the key.
+  pub fn index(&self) -> usize {
+    match *self {
+      ObjectKeyWarning::NeedsSpecialHandling { index, .. }
+      | ObjectKeyWarning::ShouldAvoid { index, .. }
+      | ObjectKeyWarning::ControlCharacter { index, .. }
+      | ObjectKeyWarning::DotSegment { index } => index,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common_testing::assert;
+
+  #[test]
+  fn bucket_name_labels() {
+    assert::equal(
+      validate_bucket_name("my..bucket"),
+      Err(InvalidBucketNameError::InvalidLabel { index: 2 }),
+    );
+    assert::equal(
+      validate_bucket_name("my-.bucket"),
+      Err(InvalidBucketNameError::InvalidLabel { index: 2 }),
+    );
+    assert::equal(
+      validate_bucket_name("-bucket"),
+      Err(InvalidBucketNameError::InvalidBoundary {
+        index: 0,
+        character: '-',
+      }),
+    );
+    assert::equal(
+      validate_bucket_name("bucket."),
+      Err(InvalidBucketNameError::InvalidBoundary {
+        index: 6,
+        character: '.',
+      }),
+    );
+  }
+
+  #[test]
+  fn bucket_name_reserved() {
+    assert::equal(
+      validate_bucket_name("bucket-s3alias"),
+      Err(InvalidBucketNameError::ReservedSuffix("-s3alias")),
+    );
+    assert::equal(
+      validate_bucket_name("sthree-bucket"),
+      Err(InvalidBucketNameError::ReservedPrefix("sthree-")),
+    );
+    // Not an IP address, so allowed.
+    assert::equal(
+      validate_bucket_name("192.168.5.256"),
+      Ok(vec![BucketNameWarning::Periods]),
+    );
+    assert::equal(
+      validate_bucket_name(&"a".repeat(64)),
+      Err(InvalidBucketNameError::Length(64)),
+    );
+  }
+
+  #[test]
+  fn object_key_warnings_are_in_order() {
+    assert::equal(
+      validate_object_key("./a\tb#é"),
+      Ok(vec![
+        ObjectKeyWarning::DotSegment { index: 0 },
+        ObjectKeyWarning::ControlCharacter {
+          index: 3,
+          character: '\t',
+        },
+        ObjectKeyWarning::ShouldAvoid {
+          index: 5,
+          character: '#',
+        },
+        ObjectKeyWarning::ShouldAvoid {
+          index: 6,
+          character: 'é',
+        },
+      ]),
+    );
+  }
+
+  #[test]
+  fn object_key_length_is_bytes() {
+    // 512 two-byte characters is 1024 bytes.
+    assert::ok(&validate_object_key(&"é".repeat(512)));
+    assert::equal(
+      validate_object_key(&"é".repeat(513)),
+      Err(InvalidObjectKeyError::TooLong(1026)),
+    );
+  }
+}
@@ -1,4 +1,7 @@
 pub mod test_get_object;
 pub mod test_list_objects;
 pub mod test_put_object;
+pub mod test_region;
+pub mod test_s3_sign;
+pub mod test_select_object;
 mod test_util;
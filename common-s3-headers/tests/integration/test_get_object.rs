@@ -1,16 +1,14 @@
 use crate::test_util::{assert, setup};
-use common_s3_headers::{self, S3HeadersBuilder};
-use std::str::FromStr;
-use url::Url;
+use common_s3_headers::{self, mock_server::MockS3ServerBuilder, AuthMode, S3HeadersBuilder};
 
 #[test]
 fn test_get_object() {
-  let (access_key, secret_key, region) = setup::get_config_from_env("TEST_S3");
-  let url = Url::from_str("https://jsonlog.s3.amazonaws.com/test/test.json").unwrap();
+  let s3 = setup::get_test_s3();
+  let url = s3.url("test/test.json");
   let headers = S3HeadersBuilder::new(&url)
-    .set_access_key(&access_key)
-    .set_secret_key(&secret_key)
-    .set_region(&region)
+    .set_access_key(&s3.access_key)
+    .set_secret_key(&s3.secret_key)
+    .set_region(&s3.region)
     .set_method("GET")
     .set_service("s3")
     .build();
@@ -24,13 +22,13 @@ fn test_get_object() {
 
 #[test]
 fn test_get_object_range_with_start() {
-  let (access_key, secret_key, region) = setup::get_config_from_env("TEST_S3");
-  let url = Url::from_str("https://jsonlog.s3.amazonaws.com/test/test.json").unwrap();
+  let s3 = setup::get_test_s3();
+  let url = s3.url("test/test.json");
   let range_headers = common_s3_headers::get_range_headers(1, None);
   let headers = S3HeadersBuilder::new(&url)
-    .set_access_key(&access_key)
-    .set_secret_key(&secret_key)
-    .set_region(&region)
+    .set_access_key(&s3.access_key)
+    .set_secret_key(&s3.secret_key)
+    .set_region(&s3.region)
     .set_method("GET")
     .set_service("s3")
     .set_headers(&range_headers)
@@ -44,13 +42,13 @@ fn test_get_object_range_with_start() {
 
 #[test]
 fn test_get_object_range_with_end() {
-  let (access_key, secret_key, region) = setup::get_config_from_env("TEST_S3");
-  let url = Url::from_str("https://jsonlog.s3.amazonaws.com/test/test.json").unwrap();
+  let s3 = setup::get_test_s3();
+  let url = s3.url("test/test.json");
   let range_headers = common_s3_headers::get_range_headers(1, Some(2));
   let headers = S3HeadersBuilder::new(&url)
-    .set_access_key(&access_key)
-    .set_secret_key(&secret_key)
-    .set_region(&region)
+    .set_access_key(&s3.access_key)
+    .set_secret_key(&s3.secret_key)
+    .set_region(&s3.region)
     .set_method("GET")
     .set_service("s3")
     .set_headers(&range_headers)
@@ -61,3 +59,72 @@ fn test_get_object_range_with_end() {
   assert::content_type(response_headers, "application/json");
   assert::equal(body, "\"a");
 }
+
+#[test]
+fn test_get_object_anonymous_from_public_bucket() {
+  let server = MockS3ServerBuilder::new()
+    .set_public_bucket("public")
+    .set_bucket("private")
+    .build()
+    .unwrap();
+  server.put_object("public", "data.csv", b"a,1\n".to_vec(), "text/csv");
+  server.put_object("private", "data.csv", b"a,1\n".to_vec(), "text/csv");
+
+  let url = server.object_url("public", "data.csv");
+  let headers = S3HeadersBuilder::new(&url)
+    .set_auth_mode(AuthMode::Anonymous)
+    .set_method("GET")
+    .build();
+  assert::equal(headers.iter().any(|(k, _)| *k == "Authorization"), false);
+  let (status_code, _, body) = assert::request_get(url, headers);
+  assert::equal(status_code, 200);
+  assert::equal(body, "a,1\n");
+
+  let url = server.object_url("private", "data.csv");
+  let headers = S3HeadersBuilder::new(&url)
+    .set_auth_mode(AuthMode::Anonymous)
+    .set_method("GET")
+    .build();
+  let response = reqwest::blocking::Client::new()
+    .get(url)
+    .headers(assert::to_reqwest_headers(headers))
+    .send()
+    .unwrap();
+  assert::equal(response.status().as_u16(), 403);
+}
+
+#[test]
+fn test_get_object_requester_pays() {
+  let server = MockS3ServerBuilder::new()
+    .set_requester_pays_bucket("shared")
+    .build()
+    .unwrap();
+  server.put_object("shared", "data.csv", b"a,1\n".to_vec(), "text/csv");
+  let url = server.object_url("shared", "data.csv");
+  let builder = S3HeadersBuilder::new(&url)
+    .set_access_key(server.access_key())
+    .set_secret_key(server.secret_key())
+    .set_region(server.region())
+    .set_method("GET")
+    .set_service("s3");
+
+  let response = reqwest::blocking::Client::new()
+    .get(url.clone())
+    .headers(assert::to_reqwest_headers(builder.clone().build()))
+    .send()
+    .unwrap();
+  assert::equal(response.status().as_u16(), 403);
+
+  let (status_code, _, body) = assert::request_get(url.clone(), builder.clone().set_requester_pays(true).build());
+  assert::equal(status_code, 200);
+  assert::equal(body, "a,1\n");
+
+  let presigned = builder.set_requester_pays(true).build_presigned_url(60);
+  assert::equal(
+    presigned.query().unwrap().contains("x-amz-request-payer=requester"),
+    true,
+  );
+  let (status_code, _, body) = assert::request_get(presigned, Vec::new());
+  assert::equal(status_code, 200);
+  assert::equal(body, "a,1\n");
+}
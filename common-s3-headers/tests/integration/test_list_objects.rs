@@ -1,8 +1,6 @@
 use crate::test_util::{assert, setup};
-use common_s3_headers::{self, S3HeadersBuilder};
+use common_s3_headers::{self, mock_server::MockS3ServerBuilder, ListObjectsV2Paginator, S3HeadersBuilder};
 use serde::Deserialize;
-use std::str::FromStr;
-use url::Url;
 
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
@@ -52,12 +50,12 @@ fn test_aws_structures() {
 
 #[test]
 fn test_list_objects() {
-  let (access_key, secret_key, region) = setup::get_config_from_env("TEST_S3");
-  let url = Url::from_str("https://jsonlog.s3.amazonaws.com/").unwrap();
+  let s3 = setup::get_test_s3();
+  let url = s3.url("");
   let headers = S3HeadersBuilder::new(&url)
-    .set_access_key(&access_key)
-    .set_secret_key(&secret_key)
-    .set_region(&region)
+    .set_access_key(&s3.access_key)
+    .set_secret_key(&s3.secret_key)
+    .set_region(&s3.region)
     .set_method("GET")
     .set_service("s3")
     .build();
@@ -76,12 +74,12 @@ fn test_list_objects() {
 
 #[test]
 fn test_list_objects_v2() {
-  let (access_key, secret_key, region) = setup::get_config_from_env("TEST_S3");
-  let url = Url::from_str("https://jsonlog.s3.amazonaws.com/?list_type=2").unwrap();
+  let s3 = setup::get_test_s3();
+  let url = s3.url("?list_type=2");
   let headers = S3HeadersBuilder::new(&url)
-    .set_access_key(&access_key)
-    .set_secret_key(&secret_key)
-    .set_region(&region)
+    .set_access_key(&s3.access_key)
+    .set_secret_key(&s3.secret_key)
+    .set_region(&s3.region)
     .set_method("GET")
     .set_service("s3")
     .build();
@@ -97,3 +95,44 @@ fn test_list_objects_v2() {
   assert::ok(&result);
   // println!("{:#?}", result);
 }
+
+#[test]
+fn test_list_objects_v2_paginator() {
+  let server = MockS3ServerBuilder::new().set_bucket("jsonlog").build().unwrap();
+  for i in 0..25 {
+    let key = format!("logs/{:02} & more.json", i);
+    server.put_object("jsonlog", &key, b"{}".to_vec(), "application/json");
+  }
+  server.put_object("jsonlog", "logs/2023/a.json", b"{}".to_vec(), "application/json");
+  server.put_object("jsonlog", "logs/~a+b c.json", b"{}".to_vec(), "application/json");
+  server.put_object("jsonlog", "other.json", b"{}".to_vec(), "application/json");
+  let bucket_url = server.bucket_url("jsonlog");
+  let paginator = ListObjectsV2Paginator::new(&bucket_url)
+    .set_access_key(server.access_key())
+    .set_secret_key(server.secret_key())
+    .set_region(server.region())
+    .set_prefix("logs/")
+    .set_delimiter("/")
+    .set_max_keys(10);
+
+  let mut page_count = 0;
+  let mut keys = Vec::new();
+  let mut common_prefixes = Vec::new();
+  for page in paginator.pages(|request| {
+    let (status_code, _, body) = assert::request_get(request.url.clone(), request.headers.clone());
+    assert::equal(status_code, 200);
+    Ok::<_, ()>(body)
+  }) {
+    let page = page.unwrap();
+    page_count += 1;
+    keys.extend(page.objects.into_iter().map(|object| object.key));
+    common_prefixes.extend(page.common_prefixes);
+  }
+
+  assert::equal(page_count, 3);
+  assert::equal(keys.len(), 26);
+  assert::equal(keys[0].as_str(), "logs/00 & more.json");
+  assert::equal(keys[24].as_str(), "logs/24 & more.json");
+  assert::equal(keys[25].as_str(), "logs/~a+b c.json");
+  assert::equal(common_prefixes, vec!["logs/2023/".to_owned()]);
+}
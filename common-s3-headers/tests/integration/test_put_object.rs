@@ -1,19 +1,21 @@
 use crate::test_util::{assert, setup};
-use common_s3_headers::{self, S3HeadersBuilder};
+use common_s3_headers::{
+  self, aws_canonical::HeaderPolicy, mock_server::MockS3ServerBuilder, CannedAcl, ContentDisposition, Grantee, Grants,
+  Provider, S3HeadersBuilder, SignatureDifference, Tagging, UserMetadata,
+};
 use std::str::FromStr;
-use url::Url;
 
 #[test]
 fn test_put_object() {
-  let (access_key, secret_key, region) = setup::get_config_from_env("TEST_S3");
+  let s3 = setup::get_test_s3();
 
-  let url = Url::from_str("https://jsonlog.s3.amazonaws.com/test/test2.json").unwrap();
+  let url = s3.url("test/test2.json");
   let content = "{\"c\":\"d\"}\n".as_bytes().to_vec();
   let sha = common_s3_headers::aws_math::get_sha256(&content);
   let headers = S3HeadersBuilder::new(&url)
-    .set_access_key(&access_key)
-    .set_secret_key(&secret_key)
-    .set_region(&region)
+    .set_access_key(&s3.access_key)
+    .set_secret_key(&s3.secret_key)
+    .set_region(&s3.region)
     .set_method("PUT")
     .set_service("s3")
     // Only borrowed should work.
@@ -30,14 +32,14 @@ const PUT_METHOD: &str = "PUT";
 
 #[test]
 fn test_put_object_with_sha_shortcut() {
-  let (access_key, secret_key, region) = setup::get_config_from_env("TEST_S3");
+  let s3 = setup::get_test_s3();
 
-  let url = Url::from_str("https://jsonlog.s3.amazonaws.com/test/test2.json").unwrap();
+  let url = s3.url("test/test2.json");
   let content = "{\"c\":\"d\"}\n".as_bytes().to_vec();
   let headers = S3HeadersBuilder::new(&url)
-    .set_access_key(&access_key)
-    .set_secret_key(&secret_key)
-    .set_region(&region)
+    .set_access_key(&s3.access_key)
+    .set_secret_key(&s3.secret_key)
+    .set_region(&s3.region)
     // This const should be okay.
     .set_method(PUT_METHOD)
     .set_service("s3")
@@ -53,13 +55,13 @@ fn test_put_object_with_sha_shortcut() {
 #[test]
 fn test_put_object_full() {
   // Build the S3 headers
-  let (access_key, secret_key, region) = setup::get_config_from_env("TEST_S3");
-  let url = Url::from_str("https://jsonlog.s3.amazonaws.com/test/test2.json").unwrap();
+  let s3 = setup::get_test_s3();
+  let url = s3.url("test/test2.json");
   let content = "{\"c\":\"d\"}\n".as_bytes().to_vec();
   let headers = S3HeadersBuilder::new(&url)
-    .set_access_key(&access_key)
-    .set_secret_key(&secret_key)
-    .set_region(&region)
+    .set_access_key(&s3.access_key)
+    .set_secret_key(&s3.secret_key)
+    .set_region(&s3.region)
     // This const should be okay.
     .set_method(PUT_METHOD)
     .set_service("s3")
@@ -87,3 +89,138 @@ fn test_put_object_full() {
   let body = response.text().unwrap();
   println!("{}\n{:#?}\n{}", status_code, response_headers, body);
 }
+
+#[test]
+fn test_put_object_with_metadata_tagging_and_disposition() {
+  let server = MockS3ServerBuilder::new().set_bucket("jsonlog").build().unwrap();
+  let url = server.object_url("jsonlog", "Résumé 2024.json");
+  let content = b"{}".to_vec();
+  let metadata = UserMetadata::new()
+    .set_value("original-name", "Résumé 2024.json")
+    .to_headers()
+    .unwrap();
+  let headers = S3HeadersBuilder::new(&url)
+    .set_access_key(server.access_key())
+    .set_secret_key(server.secret_key())
+    .set_region(server.region())
+    .set_method("PUT")
+    .set_service("s3")
+    .set_payload_hash_with_content(&content)
+    .set_header_policy(HeaderPolicy::All)
+    .add_headers(metadata)
+    .add_headers(vec![
+      Tagging::new().set_tag("owner", "zoë").to_header().unwrap(),
+      ContentDisposition::attachment()
+        .set_filename("Résumé 2024.json")
+        .to_header(),
+    ])
+    .build_headers();
+  let signed_headers = headers.get("Authorization").unwrap().to_owned();
+
+  let mut request = reqwest::blocking::Client::new().put(url).body(content);
+  for (name, value) in headers.iter() {
+    request = request.header(name, value);
+  }
+  let response = request.send().unwrap();
+
+  assert::equal(response.status().as_u16(), 200);
+  assert!(signed_headers.contains("content-disposition;"));
+  assert!(signed_headers.contains("x-amz-meta-original-name;"));
+  assert!(signed_headers.contains("x-amz-tagging"));
+}
+
+#[test]
+fn test_put_object_with_acl_headers() {
+  let server = MockS3ServerBuilder::new().set_bucket("jsonlog").build().unwrap();
+  let content = b"{}".to_vec();
+
+  for acl_headers in [
+    vec![CannedAcl::BucketOwnerFullControl.to_header()],
+    Grants::new()
+      .set_read(Grantee::all_users())
+      .set_full_control(Grantee::email_address("partner@example.com"))
+      .to_headers(),
+  ] {
+    let url = server.object_url("jsonlog", "shared.json");
+    let headers = S3HeadersBuilder::new(&url)
+      .set_access_key(server.access_key())
+      .set_secret_key(server.secret_key())
+      .set_region(server.region())
+      .set_method("PUT")
+      .set_service("s3")
+      .set_payload_hash_with_content(&content)
+      .set_headers(acl_headers)
+      .build();
+
+    let (status_code, _, _) = assert::request_put(url, headers, content.clone());
+    assert::equal(status_code, 200);
+  }
+}
+
+#[test]
+fn test_put_object_signature_mismatch_diagnosis() {
+  let server = MockS3ServerBuilder::new().set_bucket("jsonlog").build().unwrap();
+  let content = b"{}".to_vec();
+  let url = server.object_url("jsonlog", "diagnosed.json");
+  let (headers, trace) = common_s3_headers::get_signed_headers_with_trace(
+    S3HeadersBuilder::new(&url)
+      .set_access_key(server.access_key())
+      .set_secret_key(server.secret_key())
+      .set_region(server.region())
+      .set_method("PUT")
+      .set_service("s3")
+      .set_payload_hash_with_content(&content)
+      .set_header("Content-Type", "application/json")
+      .set_header_policy(HeaderPolicy::All),
+  );
+
+  // Something between us and S3 changed a signed header.
+  let mut headers = headers.into_static_vec();
+  for (k, v) in headers.iter_mut() {
+    if *k == "Content-Type" {
+      *v = "text/plain".to_owned();
+    }
+  }
+  let response = reqwest::blocking::Client::new()
+    .put(url)
+    .headers(assert::to_reqwest_headers(headers))
+    .body(content)
+    .send()
+    .unwrap();
+  assert::equal(response.status().as_u16(), 403);
+
+  let mismatch = common_s3_headers::parse_signature_mismatch(&response.text().unwrap()).unwrap();
+  let report = common_s3_headers::diagnose_signature_mismatch(&mismatch, &trace);
+  assert::equal(
+    report.differences.clone(),
+    vec![SignatureDifference::HeaderValue {
+      name: "content-type".to_owned(),
+      local: "application/json".to_owned(),
+      remote: "text/plain".to_owned(),
+    }],
+  );
+  assert!(report
+    .to_string()
+    .contains("- content-type:application/json\n+ content-type:text/plain\n"));
+}
+
+#[test]
+fn test_put_object_with_provider() {
+  let server = MockS3ServerBuilder::new().set_bucket("jsonlog").build().unwrap();
+  let endpoint = server.endpoint().to_string();
+  let provider = Provider::minio(&endpoint).set_region(server.region());
+  let url = provider.object_url("jsonlog", "provider/a b.json").unwrap();
+  let content = b"{}".to_vec();
+
+  let headers = S3HeadersBuilder::new(&url)
+    .set_provider(&provider)
+    .set_access_key(server.access_key())
+    .set_secret_key(server.secret_key())
+    .set_method("PUT")
+    .set_payload_hash_with_content(&content)
+    .build();
+
+  let (status_code, _, _) = assert::request_put(url, headers, content.clone());
+  assert::equal(status_code, 200);
+  assert::equal(server.get_object("jsonlog", "provider/a b.json").unwrap().body, content);
+}
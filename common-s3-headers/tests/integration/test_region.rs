@@ -0,0 +1,48 @@
+use crate::test_util::assert;
+use common_s3_headers::{mock_server::MockS3ServerBuilder, HeadBucketBuilder, RegionCache, S3HeadersBuilder};
+
+#[test]
+fn test_head_bucket_finds_region_and_retries() {
+  let server = MockS3ServerBuilder::new()
+    .set_region("eu-west-1")
+    .set_bucket("jsonlog")
+    .build()
+    .unwrap();
+  server.put_object("jsonlog", "test.json", b"{}".to_vec(), "application/json");
+  let endpoint = server.endpoint();
+  let mut cache = RegionCache::new(16);
+
+  // Probe with the wrong region.
+  let request = HeadBucketBuilder::new("jsonlog")
+    .set_access_key(server.access_key())
+    .set_secret_key(server.secret_key())
+    .set_endpoint(&endpoint)
+    .build()
+    .unwrap();
+  let response = reqwest::blocking::Client::new()
+    .head(request.url)
+    .headers(assert::to_reqwest_headers(request.headers))
+    .send()
+    .unwrap();
+  assert::equal(response.status().as_u16(), 400);
+  let headers: Vec<(String, String)> = response
+    .headers()
+    .iter()
+    .map(|(k, v)| (k.as_str().to_owned(), v.to_str().unwrap().to_owned()))
+    .collect();
+  let region = cache.update_from_response("jsonlog", &headers, "").unwrap().to_owned();
+  assert::equal(region.as_str(), "eu-west-1");
+
+  // Re-sign with the region it found.
+  let url = server.object_url("jsonlog", "test.json");
+  let headers = S3HeadersBuilder::new(&url)
+    .set_access_key(server.access_key())
+    .set_secret_key(server.secret_key())
+    .set_region(&region)
+    .set_method("GET")
+    .set_service("s3")
+    .build();
+  let (status_code, _, body) = assert::request_get(url, headers);
+  assert::equal(status_code, 200);
+  assert::equal(body, "{}");
+}
@@ -0,0 +1,68 @@
+use crate::test_util::assert;
+use common_s3_headers::mock_server::{MockS3Server, MockS3ServerBuilder};
+use std::process::Command;
+
+fn s3_sign(server: &MockS3Server, args: &[&str]) -> (String, String) {
+  let output = Command::new(env!("CARGO_BIN_EXE_s3-sign"))
+    .args(args)
+    .env("AWS_ACCESS_KEY_ID", server.access_key())
+    .env("AWS_SECRET_ACCESS_KEY", server.secret_key())
+    .env("AWS_REGION", server.region())
+    .env_remove("AWS_SESSION_TOKEN")
+    .env_remove("AWS_PROFILE")
+    .output()
+    .unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  (
+    String::from_utf8(output.stdout).unwrap(),
+    String::from_utf8(output.stderr).unwrap(),
+  )
+}
+
+#[test]
+fn test_s3_sign_prints_headers_that_work() {
+  let server = MockS3ServerBuilder::new().set_bucket("jsonlog").build().unwrap();
+  server.put_object("jsonlog", "test.json", b"{}".to_vec(), "application/json");
+  let url = server.object_url("jsonlog", "test.json");
+
+  let (stdout, stderr) = s3_sign(&server, &["--trace", "-H", "Range: bytes=0-0", url.as_str()]);
+  let mut request = reqwest::blocking::Client::new().get(url);
+  for line in stdout.lines() {
+    let (name, value) = line.split_once(": ").unwrap();
+    request = request.header(name, value);
+  }
+  let response = request.send().unwrap();
+
+  assert::equal(response.status().as_u16(), 206);
+  assert::equal(response.text().unwrap(), "{");
+  assert!(stderr.contains("Canonical request:\nGET\n/jsonlog/test.json\n"));
+  assert!(stderr.contains("Signed headers: host;range;x-amz-content-sha256;x-amz-date"));
+}
+
+#[test]
+fn test_s3_sign_presigns_urls_that_work() {
+  let server = MockS3ServerBuilder::new().set_bucket("jsonlog").build().unwrap();
+  server.put_object("jsonlog", "test.json", b"{}".to_vec(), "application/json");
+  let url = server.object_url("jsonlog", "test.json");
+
+  let (stdout, _) = s3_sign(&server, &["--presign", "60", url.as_str()]);
+  let response = reqwest::blocking::get(stdout.trim()).unwrap();
+
+  assert::equal(response.status().as_u16(), 200);
+  assert::equal(response.text().unwrap(), "{}");
+}
+
+#[test]
+fn test_s3_sign_presigned_urls_expire() {
+  let server = MockS3ServerBuilder::new().set_bucket("jsonlog").build().unwrap();
+  let url = server.object_url("jsonlog", "test.json");
+
+  let (stdout, _) = s3_sign(
+    &server,
+    &["--presign", "60", "--date", "20130524T000000Z", url.as_str()],
+  );
+  let response = reqwest::blocking::get(stdout.trim()).unwrap();
+
+  assert::equal(response.status().as_u16(), 403);
+  assert!(response.text().unwrap().contains("Request has expired"));
+}
@@ -0,0 +1,53 @@
+use crate::test_util::assert;
+use common_s3_headers::{
+  decode_select_events, mock_server::MockS3ServerBuilder, select_object_content_url, CsvInput, CsvOutput,
+  FileHeaderInfo, S3HeadersBuilder, SelectEvent, SelectInput, SelectObjectContentBuilder, SelectOutput, SelectStats,
+};
+
+#[test]
+fn test_select_object_content() {
+  let server = MockS3ServerBuilder::new().set_bucket("jsonlog").build().unwrap();
+  let content = b"name,age\nalice,31\nbob,29\n".to_vec();
+  server.put_object("jsonlog", "people.csv", content.clone(), "text/csv");
+
+  let select = SelectObjectContentBuilder::new("SELECT * FROM S3Object s")
+    .set_input(SelectInput::Csv(
+      CsvInput::new().set_file_header_info(FileHeaderInfo::None),
+    ))
+    .set_output(SelectOutput::Csv(CsvOutput::new()));
+  let body = select.to_xml();
+  let url = select_object_content_url(&server.object_url("jsonlog", "people.csv"));
+  let headers = S3HeadersBuilder::new(&url)
+    .set_access_key(server.access_key())
+    .set_secret_key(server.secret_key())
+    .set_region(server.region())
+    .set_method("POST")
+    .set_service("s3")
+    .set_payload_hash_with_content(body.as_bytes())
+    .set_headers(select.to_headers())
+    .build();
+
+  let client = reqwest::blocking::Client::new();
+  let response = client
+    .post(url)
+    .headers(assert::to_reqwest_headers(headers))
+    .body(body)
+    .send()
+    .unwrap();
+  assert::equal(response.status().as_u16(), 200);
+  let events = decode_select_events(&response.bytes().unwrap()).unwrap();
+
+  let size = content.len() as u64;
+  assert::equal(
+    events,
+    vec![
+      SelectEvent::Records(content),
+      SelectEvent::Stats(SelectStats {
+        bytes_scanned: size,
+        bytes_processed: size,
+        bytes_returned: size,
+      }),
+      SelectEvent::End,
+    ],
+  );
+}
@@ -19,7 +19,7 @@ struct AwsError {
 }
 
 /// We shouldn't care which library is used for testing.
-fn to_reqwest_headers(request_headers: Vec<(&'static str, String)>) -> HeaderMap {
+pub fn to_reqwest_headers(request_headers: Vec<(&'static str, String)>) -> HeaderMap {
   HeaderMap::from_iter(
     request_headers
       .into_iter()
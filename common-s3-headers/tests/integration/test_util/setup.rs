@@ -1,3 +1,52 @@
+use common_s3_headers::mock_server::{MockS3Server, MockS3ServerBuilder};
+use url::Url;
+
+/// Where the integration tests send requests. By default, an in-memory mock
+/// server. Set `TEST_S3_LIVE=1` to use the real bucket and the `.env`
+/// credentials instead.
+pub struct TestS3 {
+  pub access_key: String,
+  pub secret_key: String,
+  pub region: String,
+  bucket_url: Url,
+  _server: Option<MockS3Server>,
+}
+
+impl TestS3 {
+  /// Gets the URL for a key, or a query on the bucket such as `?list-type=2`.
+  pub fn url(&self, path: &str) -> Url {
+    self.bucket_url.join(path).unwrap()
+  }
+}
+
+pub fn get_test_s3() -> TestS3 {
+  if std::env::var("TEST_S3_LIVE").is_ok() {
+    let (access_key, secret_key, region) = get_config_from_env("TEST_S3");
+    return TestS3 {
+      access_key,
+      secret_key,
+      region,
+      bucket_url: Url::parse("https://jsonlog.s3.amazonaws.com/").unwrap(),
+      _server: None,
+    };
+  }
+
+  let server = MockS3ServerBuilder::new().set_bucket("jsonlog").build().unwrap();
+  server.put_object(
+    "jsonlog",
+    "test/test.json",
+    "{\"a\":\"b\"}\n".as_bytes().to_vec(),
+    "application/json",
+  );
+  TestS3 {
+    access_key: server.access_key().to_owned(),
+    secret_key: server.secret_key().to_owned(),
+    region: server.region().to_owned(),
+    bucket_url: server.bucket_url("jsonlog"),
+    _server: Some(server),
+  }
+}
+
 pub fn get_config_from_env(prefix: &str) -> (String, String, String) {
   dotenvy::dotenv().unwrap();
 